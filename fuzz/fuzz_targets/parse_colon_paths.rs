@@ -0,0 +1,18 @@
+#![no_main]
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let paths = systemd_directories::parse_colon_paths(OsStr::from_bytes(data));
+    for path in &paths {
+        let bytes = path.as_os_str().as_bytes();
+        assert!(!bytes.is_empty());
+        assert!(!bytes.contains(&b':'));
+    }
+
+    let non_empty = data.split(|&b| b == b':').filter(|s| !s.is_empty()).count();
+    assert_eq!(paths.len(), non_empty);
+});
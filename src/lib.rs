@@ -18,13 +18,53 @@
 //! let dirs = SystemdDirs::new();
 //! let runtime_dir = dirs.runtime_dir();
 //! ```
+//!
+//! # Features
+//! Each kind of directory has a feature, all of which are enabled by default: `runtime`, `state`, `cache`, `logs`,
+//! and `config`. Disabling a feature removes the accessors for that kind and stops [`SystemdDirs::new`] from reading
+//! its environment variable, which is useful for minimal builds.
+//!
+//! The `serde` feature enables `SystemdDirs::from_json`, which reads directories from a JSON document for environments
+//! that emulate systemd without setting its environment variables.
+//!
+//! The `test-util` feature enables test-support utilities such as `EnvGuard`.
 
 #![deny(missing_docs)]
 #![forbid(unsafe_code)]
 #![warn(clippy::missing_docs_in_private_items)]
 
+use std::array;
+use std::borrow::{Borrow, Cow};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::{Path, PathBuf};
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::iter;
+use std::mem;
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+#[cfg(feature = "runtime")]
+use std::thread;
+use std::time::SystemTime;
+#[cfg(feature = "runtime")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
 
 /// A struct to hold colon-separated paths.
 struct ColonSeparatedPaths {
@@ -39,16 +79,584 @@ impl ColonSeparatedPaths {
     }
 
     /// Returns a new [`ColonSeparatedPaths`] struct with the environment variable `env_key`.
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
     fn from_env_key(env_key: &str) -> Self {
         Self::new(env::var(env_key).unwrap_or_default())
     }
 
     /// Returns an iterator over the paths.
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
     fn iter(&self) -> impl Iterator<Item = &Path> {
-        self.paths
-            .split(':')
-            .map(Path::new)
-            .filter(|p| !p.as_os_str().is_empty())
+        self.segments().filter(|p| !p.as_os_str().is_empty())
+    }
+
+    /// Returns an iterator over all segments between colons, including empty ones.
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    fn segments(&self) -> impl Iterator<Item = &Path> {
+        self.paths.split(':').map(Path::new)
+    }
+
+    /// Returns the paths parsed according to `options`.
+    fn parse(&self, options: &ParseOptions) -> Vec<PathBuf> {
+        options
+            .segments(&self.paths)
+            .iter()
+            .map(|segment| segment.as_ref())
+            .filter(|segment| options.keep_empty || !segment.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+/// Options controlling how [`ColonSeparatedPaths`] are parsed.
+#[derive(Debug, Clone)]
+struct ParseOptions {
+    /// The characters that separate paths, which is only `:` by default.
+    separators: Vec<char>,
+
+    /// Whether empty segments, such as from a leading or doubled colon, are kept as empty paths.
+    keep_empty: bool,
+
+    /// Whether a single matching pair of surrounding quotes is removed from each segment.
+    unquote: bool,
+
+    /// Whether leading and trailing whitespace is removed from each segment, before any quotes are removed.
+    trim: bool,
+
+    /// Whether a backslash escapes a following separator or backslash, so that it is kept in the segment.
+    escapes: bool,
+
+    /// Whether a variable set to the empty string is treated as unset rather than as present with no paths.
+    empty_is_unset: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            separators: vec![':'],
+            keep_empty: false,
+            unquote: false,
+            trim: false,
+            escapes: false,
+            empty_is_unset: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Splits `value` into its segments between separators, including empty ones, with each segment cleaned as by
+    /// [`Self::clean`].
+    fn segments<'a>(&self, value: &'a str) -> Vec<Cow<'a, str>> {
+        let segments: Vec<Cow<str>> = if self.escapes {
+            split_escaped(value, &self.separators)
+                .into_iter()
+                .map(Cow::Owned)
+                .collect()
+        } else {
+            value
+                .split(|c| self.separators.contains(&c))
+                .map(Cow::Borrowed)
+                .collect()
+        };
+
+        if !self.trim && !self.unquote {
+            return segments;
+        }
+
+        segments
+            .into_iter()
+            .map(|segment| match segment {
+                Cow::Borrowed(segment) => Cow::Borrowed(self.clean(segment)),
+                Cow::Owned(segment) => Cow::Owned(self.clean(&segment).to_owned()),
+            })
+            .collect()
+    }
+
+    /// Trims and then unquotes `segment` if `trim` and `unquote` are set.
+    fn clean<'a>(&self, segment: &'a str) -> &'a str {
+        let segment = if self.trim { segment.trim() } else { segment };
+        if self.unquote {
+            unquote(segment)
+        } else {
+            segment
+        }
+    }
+}
+
+/// Splits `value` on unescaped `separators`, replacing a backslash followed by a separator or backslash with that
+/// character.
+///
+/// A backslash followed by any other character, or at the end of `value`, is kept as is.
+fn split_escaped(value: &str, separators: &[char]) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        let segment = segments.last_mut().expect("segments is never empty");
+        match c {
+            '\\' => match chars.next_if(|&next| next == '\\' || separators.contains(&next)) {
+                Some(escaped) => segment.push(escaped),
+                None => segment.push(c),
+            },
+            c if separators.contains(&c) => segments.push(String::new()),
+            c => segment.push(c),
+        }
+    }
+
+    segments
+}
+
+/// Removes a single matching pair of surrounding single or double quotes from `value`, if present.
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| {
+            value
+                .strip_prefix(quote)
+                .and_then(|v| v.strip_suffix(quote))
+        })
+        .unwrap_or(value)
+}
+
+/// Parses the contents of a dotenv-style file into a map of variables.
+///
+/// Blank lines and lines starting with `#` are ignored. Every other line must be of the form `KEY=VALUE`. Surrounding
+/// whitespace is trimmed from keys and values, and a single matching pair of surrounding quotes is removed from values.
+/// Later assignments to the same key override earlier ones.
+fn parse_env_file(contents: &str) -> io::Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: expected KEY=VALUE", index + 1),
+            )
+        })?;
+
+        vars.insert(key.trim().to_string(), unquote(value.trim()).to_string());
+    }
+
+    Ok(vars)
+}
+
+/// Parses the contents of a `/proc/<pid>/environ` file into a map of variables.
+///
+/// The file contains NUL-terminated `KEY=VALUE` entries. Entries without a `=` are ignored, and values that are not
+/// valid UTF-8 are treated as empty, matching how [`SystemdDirs::new`] reads the environment.
+#[cfg(target_os = "linux")]
+fn parse_environ(environ: &[u8]) -> HashMap<String, String> {
+    environ
+        .split(|&b| b == 0)
+        .filter_map(|entry| {
+            let separator = entry.iter().position(|&b| b == b'=')?;
+            let key = String::from_utf8(entry[..separator].to_vec()).ok()?;
+            let value = String::from_utf8(entry[separator + 1..].to_vec()).unwrap_or_default();
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// Parses the systemd version from the output of `systemctl --version`.
+///
+/// The first line has the form `systemd 255 (255.4-1ubuntu8)`, where the number after `systemd` is the major version.
+#[cfg(target_os = "linux")]
+fn parse_systemd_version(output: &str) -> Option<u32> {
+    let mut words = output.lines().next()?.split_whitespace();
+    if words.next()? != "systemd" {
+        return None;
+    }
+
+    words.next()?.parse().ok()
+}
+
+/// The offset basis of 64-bit FNV-1a.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// The prime of 64-bit FNV-1a.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continues a 64-bit FNV-1a `hash` over `bytes`.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Continues a 64-bit FNV-1a `hash` over the number of `dirs` and then each one's length and bytes.
+fn fnv1a_dirs(hash: u64, dirs: &[PathBuf]) -> u64 {
+    let hash = fnv1a(hash, &(dirs.len() as u64).to_le_bytes());
+    dirs.iter().fold(hash, |hash, dir| {
+        let bytes = dir.as_os_str().as_encoded_bytes();
+        fnv1a(fnv1a(hash, &(bytes.len() as u64).to_le_bytes()), bytes)
+    })
+}
+
+/// Returns the summed size of the regular files under `dir`, recursively, without following symbolic links.
+fn disk_usage(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += disk_usage(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+
+    Ok(size)
+}
+
+/// Appends the regular files under `dir` to `files`, recursively and sorted by path, without following symbolic links.
+fn walk_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(fs::DirEntry::path);
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns each of `dirs` relative to `base` if it is under it, or unchanged otherwise, with `base` itself as `.`.
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+fn relative_to(dirs: &[PathBuf], base: &Path) -> Vec<PathBuf> {
+    dirs.iter()
+        .map(|dir| match dir.strip_prefix(base) {
+            Ok(rest) if rest.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(rest) => rest.to_path_buf(),
+            Err(_) => dir.clone(),
+        })
+        .collect()
+}
+
+/// Returns whether a uniquely named probe file can be created and removed in `dir`.
+///
+/// A directory that is read-only or denies access is not writable; any other failure is returned as an error. The probe
+/// name is unique, so concurrent probes of the same directory and probes left behind by a crash do not interfere.
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+fn is_writable(dir: &Path) -> io::Result<bool> {
+    match create_unique(dir, ".systemd-directories-probe-") {
+        Ok((_, probe)) => {
+            fs::remove_file(&probe)?;
+            Ok(true)
+        }
+        Err(e) => match e.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => Ok(false),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Returns the first of `dirs` in which a probe file can be created and removed.
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+fn first_writable<'a>(dirs: impl Iterator<Item = &'a Path>) -> io::Result<Option<&'a Path>> {
+    for dir in dirs {
+        match is_writable(dir) {
+            Ok(true) => return Ok(Some(dir)),
+            Ok(false) => continue,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(None)
+}
+
+/// The shape of the JSON documents read by [`SystemdDirs::from_json`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonDocument {
+    /// The runtime directories.
+    runtime: Option<Vec<PathBuf>>,
+
+    /// The state directories.
+    state: Option<Vec<PathBuf>>,
+
+    /// The cache directories.
+    cache: Option<Vec<PathBuf>>,
+
+    /// The logs directories.
+    logs: Option<Vec<PathBuf>>,
+
+    /// The configuration directories.
+    config: Option<Vec<PathBuf>>,
+}
+
+#[cfg(feature = "serde")]
+impl JsonDocument {
+    /// Takes the directories of `kind`, or [`None`] if the document has no key for it.
+    fn take(&mut self, kind: DirKind) -> Option<Vec<PathBuf>> {
+        match kind {
+            DirKind::Runtime => self.runtime.take(),
+            DirKind::State => self.state.take(),
+            DirKind::Cache => self.cache.take(),
+            DirKind::Logs => self.logs.take(),
+            DirKind::Config => self.config.take(),
+        }
+    }
+}
+
+/// Creates a new file in `dir` named `prefix` followed by a name that is unique to this process, returning the open
+/// file and its path.
+///
+/// The file is created exclusively, so names left behind by other processes or earlier calls are skipped.
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+fn create_unique(dir: &Path, prefix: &str) -> io::Result<(fs::File, PathBuf)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    loop {
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("{prefix}{}-{count}", process::id()));
+        match fs::File::create_new(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Parses the file mode creation mask from the contents of `/proc/<pid>/status`, which has a line like `Umask:\t0022`.
+#[cfg(target_os = "linux")]
+fn parse_umask(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Umask:"))
+        .and_then(|umask| parse_mode(umask.trim()))
+}
+
+/// Splits `value` on colons without requiring it to be valid UTF-8.
+#[cfg(unix)]
+fn split_colons_os(value: &OsStr) -> impl Iterator<Item = &OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+    value
+        .as_bytes()
+        .split(|&b| b == b':')
+        .map(OsStr::from_bytes)
+}
+
+/// Splits `value` on colons, treating values that are not valid UTF-8 as empty.
+#[cfg(not(unix))]
+fn split_colons_os(value: &OsStr) -> impl Iterator<Item = &OsStr> {
+    value
+        .to_str()
+        .unwrap_or_default()
+        .split(':')
+        .map(OsStr::new)
+}
+
+/// Returns the first non-empty colon-separated segment of `value`.
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+fn first_segment_os(value: &OsStr) -> Option<OsString> {
+    split_colons_os(value)
+        .find(|segment| !segment.is_empty())
+        .map(OsString::from)
+}
+
+/// Looks up the environment variable `key`, treating values that are not valid UTF-8 as empty.
+fn env_lookup(key: &str) -> Option<String> {
+    env::var_os(key).map(|v| v.into_string().unwrap_or_default())
+}
+
+/// The environment variable some setups use to propagate `RuntimeDirectoryPreserve` from the unit file.
+const RUNTIME_DIRECTORY_PRESERVE: &str = "RUNTIME_DIRECTORY_PRESERVE";
+
+/// Snapshots the variables of all kinds and [`RUNTIME_DIRECTORY_PRESERVE`] in a single pass over the environment, with
+/// the same semantics as [`env_lookup`].
+fn env_snapshot() -> HashMap<&'static str, String> {
+    let mut snapshot = HashMap::new();
+    for (key, value) in env::vars_os() {
+        let Some(key) = DirKind::ALL
+            .iter()
+            .map(DirKind::env_key)
+            .chain([RUNTIME_DIRECTORY_PRESERVE])
+            .find(|k| key == *k)
+        else {
+            continue;
+        };
+
+        snapshot
+            .entry(key)
+            .or_insert_with(|| value.into_string().unwrap_or_default());
+    }
+
+    snapshot
+}
+
+/// Joins `paths` with colons, the inverse of parsing [`ColonSeparatedPaths`].
+fn join_colons(paths: &[PathBuf]) -> OsString {
+    let mut joined = OsString::new();
+    for (index, path) in paths.iter().enumerate() {
+        if index > 0 {
+            joined.push(":");
+        }
+
+        joined.push(path);
+    }
+
+    joined
+}
+
+/// Returns the `file://` URL of the absolute `path`, percent-encoding every byte except unreserved characters and `/`.
+#[cfg(any(
+    feature = "runtime",
+    feature = "state",
+    feature = "cache",
+    feature = "logs",
+    feature = "config"
+))]
+fn file_url(path: &Path) -> Option<String> {
+    if !path.is_absolute() {
+        return None;
+    }
+
+    let mut url = String::from("file://");
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            url.push(char::from(byte));
+        } else {
+            url.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    Some(url)
+}
+
+/// Parses an octal access mode such as `0700`, returning [`None`] if it is malformed or exceeds `07777`.
+fn parse_mode(value: &str) -> Option<u32> {
+    if value.is_empty() || !value.bytes().all(|b| matches!(b, b'0'..=b'7')) {
+        return None;
+    }
+
+    u32::from_str_radix(value, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+}
+
+/// Returns whether `name` matches the glob `pattern`, where `*` matches any run of characters and `?` matches one.
+#[cfg(feature = "config")]
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Returns the lexical relative path from `from` to `to`, or [`None`] if only one of them is absolute.
+fn relative_path(from: &Path, to: &Path) -> Option<PathBuf> {
+    if from.is_absolute() != to.is_absolute() {
+        return None;
+    }
+
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let shared = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let relative: PathBuf = from[shared..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(to[shared..].iter().copied())
+        .collect();
+
+    if relative.as_os_str().is_empty() {
+        Some(PathBuf::from("."))
+    } else {
+        Some(relative)
+    }
+}
+
+/// Lexically normalizes `path` by collapsing repeated separators and removing trailing separators and `.` components.
+///
+/// `..` components are kept as-is since resolving them requires filesystem access. A path consisting only of `.`
+/// components normalizes to `.`.
+fn normalize(path: &Path) -> PathBuf {
+    let normalized: PathBuf = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+
+    if normalized.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        normalized
     }
 }
 
@@ -62,6 +670,7 @@ impl ColonSeparatedPaths {
 /// ```
 /// let runtime_dirs = systemd_directories::runtime_dirs();
 /// ```
+#[cfg(feature = "runtime")]
 pub fn runtime_dirs() -> Vec<PathBuf> {
     ColonSeparatedPaths::from_env_key("RUNTIME_DIRECTORY")
         .iter()
@@ -89,6 +698,7 @@ pub fn runtime_dirs() -> Vec<PathBuf> {
 ///     // --snip--
 /// }
 /// ```
+#[cfg(feature = "runtime")]
 pub fn runtime_dir() -> Option<PathBuf> {
     ColonSeparatedPaths::from_env_key("RUNTIME_DIRECTORY")
         .iter()
@@ -106,6 +716,7 @@ pub fn runtime_dir() -> Option<PathBuf> {
 /// ```
 /// let state_dirs = systemd_directories::state_dirs();
 /// ```
+#[cfg(feature = "state")]
 pub fn state_dirs() -> Vec<PathBuf> {
     ColonSeparatedPaths::from_env_key("STATE_DIRECTORY")
         .iter()
@@ -133,6 +744,7 @@ pub fn state_dirs() -> Vec<PathBuf> {
 ///     // --snip--
 /// }
 /// ```
+#[cfg(feature = "state")]
 pub fn state_dir() -> Option<PathBuf> {
     ColonSeparatedPaths::from_env_key("STATE_DIRECTORY")
         .iter()
@@ -150,6 +762,7 @@ pub fn state_dir() -> Option<PathBuf> {
 /// ```
 /// let cache_dirs = systemd_directories::cache_dirs();
 /// ```
+#[cfg(feature = "cache")]
 pub fn cache_dirs() -> Vec<PathBuf> {
     ColonSeparatedPaths::from_env_key("CACHE_DIRECTORY")
         .iter()
@@ -177,6 +790,7 @@ pub fn cache_dirs() -> Vec<PathBuf> {
 ///     // --snip--
 /// }
 /// ```
+#[cfg(feature = "cache")]
 pub fn cache_dir() -> Option<PathBuf> {
     ColonSeparatedPaths::from_env_key("CACHE_DIRECTORY")
         .iter()
@@ -194,6 +808,7 @@ pub fn cache_dir() -> Option<PathBuf> {
 /// ```
 /// let logs_dirs = systemd_directories::logs_dirs();
 /// ```
+#[cfg(feature = "logs")]
 pub fn logs_dirs() -> Vec<PathBuf> {
     ColonSeparatedPaths::from_env_key("LOGS_DIRECTORY")
         .iter()
@@ -221,6 +836,7 @@ pub fn logs_dirs() -> Vec<PathBuf> {
 ///     // --snip--
 /// }
 /// ```
+#[cfg(feature = "logs")]
 pub fn logs_dir() -> Option<PathBuf> {
     ColonSeparatedPaths::from_env_key("LOGS_DIRECTORY")
         .iter()
@@ -238,6 +854,7 @@ pub fn logs_dir() -> Option<PathBuf> {
 /// ```
 /// let config_dirs = systemd_directories::config_dirs();
 /// ```
+#[cfg(feature = "config")]
 pub fn config_dirs() -> Vec<PathBuf> {
     ColonSeparatedPaths::from_env_key("CONFIGURATION_DIRECTORY")
         .iter()
@@ -265,6 +882,7 @@ pub fn config_dirs() -> Vec<PathBuf> {
 ///     // --snip--
 /// }
 /// ```
+#[cfg(feature = "config")]
 pub fn config_dir() -> Option<PathBuf> {
     ColonSeparatedPaths::from_env_key("CONFIGURATION_DIRECTORY")
         .iter()
@@ -272,348 +890,9006 @@ pub fn config_dir() -> Option<PathBuf> {
         .map(PathBuf::from)
 }
 
-/// A struct to snapshot the environment at the time of creation.
+/// Returns the first runtime directory relative to `base`.
 ///
-/// The [`SystemdDirs`] methods return [`Path`] objects as immutable references to the paths as available when
-/// [`Self::new`] was called. This differs from the standalone functions, which return [`PathBuf`] objects.
-#[derive(Debug, Clone)]
-pub struct SystemdDirs {
-    /// All runtime directories when the struct was created.
-    runtime_dirs: Vec<PathBuf>,
+/// If the environment variable `RUNTIME_DIRECTORY` is not set, or if its first path does not start with `base`, it returns
+/// [`None`]. Otherwise, it returns the first path in the colon-separated list with `base` stripped using
+/// [`Path::strip_prefix`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # use std::path::Path;
+/// # env::set_var("RUNTIME_DIRECTORY", "/run/foo/sub");
+/// let runtime_dir = systemd_directories::runtime_dir_relative_to("/run");
+/// assert_eq!(runtime_dir.as_deref(), Some(Path::new("foo/sub")));
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dir_relative_to(base: impl AsRef<Path>) -> Option<PathBuf> {
+    runtime_dir()?.strip_prefix(base).ok().map(PathBuf::from)
+}
 
-    /// All state directories when the struct was created.
-    state_dirs: Vec<PathBuf>,
+/// Returns the first state directory relative to `base`.
+///
+/// If the environment variable `STATE_DIRECTORY` is not set, or if its first path does not start with `base`, it returns
+/// [`None`]. Otherwise, it returns the first path in the colon-separated list with `base` stripped using
+/// [`Path::strip_prefix`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # use std::path::Path;
+/// # env::set_var("STATE_DIRECTORY", "/var/lib/foo/sub");
+/// let state_dir = systemd_directories::state_dir_relative_to("/var/lib");
+/// assert_eq!(state_dir.as_deref(), Some(Path::new("foo/sub")));
+/// ```
+#[cfg(feature = "state")]
+pub fn state_dir_relative_to(base: impl AsRef<Path>) -> Option<PathBuf> {
+    state_dir()?.strip_prefix(base).ok().map(PathBuf::from)
+}
 
-    /// All cache directories when the struct was created.
-    cache_dirs: Vec<PathBuf>,
+/// Returns the first cache directory relative to `base`.
+///
+/// If the environment variable `CACHE_DIRECTORY` is not set, or if its first path does not start with `base`, it returns
+/// [`None`]. Otherwise, it returns the first path in the colon-separated list with `base` stripped using
+/// [`Path::strip_prefix`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # use std::path::Path;
+/// # env::set_var("CACHE_DIRECTORY", "/var/cache/foo/sub");
+/// let cache_dir = systemd_directories::cache_dir_relative_to("/var/cache");
+/// assert_eq!(cache_dir.as_deref(), Some(Path::new("foo/sub")));
+/// ```
+#[cfg(feature = "cache")]
+pub fn cache_dir_relative_to(base: impl AsRef<Path>) -> Option<PathBuf> {
+    cache_dir()?.strip_prefix(base).ok().map(PathBuf::from)
+}
 
-    /// All logs directories when the struct was created.
-    logs_dirs: Vec<PathBuf>,
+/// Returns the first logs directory relative to `base`.
+///
+/// If the environment variable `LOGS_DIRECTORY` is not set, or if its first path does not start with `base`, it returns
+/// [`None`]. Otherwise, it returns the first path in the colon-separated list with `base` stripped using
+/// [`Path::strip_prefix`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # use std::path::Path;
+/// # env::set_var("LOGS_DIRECTORY", "/var/log/foo/sub");
+/// let logs_dir = systemd_directories::logs_dir_relative_to("/var/log");
+/// assert_eq!(logs_dir.as_deref(), Some(Path::new("foo/sub")));
+/// ```
+#[cfg(feature = "logs")]
+pub fn logs_dir_relative_to(base: impl AsRef<Path>) -> Option<PathBuf> {
+    logs_dir()?.strip_prefix(base).ok().map(PathBuf::from)
+}
 
-    /// All configuration directories when the struct was created.
-    config_dirs: Vec<PathBuf>,
+/// Returns the first configuration directory relative to `base`.
+///
+/// If the environment variable `CONFIGURATION_DIRECTORY` is not set, or if its first path does not start with `base`, it returns
+/// [`None`]. Otherwise, it returns the first path in the colon-separated list with `base` stripped using
+/// [`Path::strip_prefix`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # use std::path::Path;
+/// # env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo/sub");
+/// let config_dir = systemd_directories::config_dir_relative_to("/etc");
+/// assert_eq!(config_dir.as_deref(), Some(Path::new("foo/sub")));
+/// ```
+#[cfg(feature = "config")]
+pub fn config_dir_relative_to(base: impl AsRef<Path>) -> Option<PathBuf> {
+    config_dir()?.strip_prefix(base).ok().map(PathBuf::from)
 }
 
-impl SystemdDirs {
-    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment.
-    ///
-    /// # Examples
-    /// ```
-    /// use systemd_directories::SystemdDirs;
-    /// let dirs = SystemdDirs::new();
-    /// ```
-    pub fn new() -> Self {
-        // TODO: Use env::vars to snapshot the environment or is separate env::var calls sufficient?
-        Self {
-            runtime_dirs: runtime_dirs(),
-            state_dirs: state_dirs(),
-            cache_dirs: cache_dirs(),
-            logs_dirs: logs_dirs(),
-            config_dirs: config_dirs(),
-        }
-    }
+/// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file, keeping empty segments.
+///
+/// This behaves like [`runtime_dirs`], but empty segments, such as from a leading or doubled colon, are returned as
+/// empty paths rather than being dropped. This is useful for diagnostics that need to detect malformed values.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("RUNTIME_DIRECTORY", ":/run/foo");
+/// let runtime_dirs = systemd_directories::runtime_dirs_preserving_empty();
+/// assert_eq!(runtime_dirs.len(), 2);
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dirs_preserving_empty() -> Vec<PathBuf> {
+    ColonSeparatedPaths::from_env_key("RUNTIME_DIRECTORY")
+        .segments()
+        .map(PathBuf::from)
+        .collect()
+}
 
-    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file.
-    ///
-    /// If the environment variable `RUNTIME_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
-    /// If it was set, it returns all paths in the colon-separated list.
-    /// To get just the first path, use [`Self::runtime_dir`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let runtime_dirs = dirs.runtime_dirs();
-    /// ```
-    pub fn runtime_dirs(&self) -> Vec<&Path> {
-        self.as_paths(&self.runtime_dirs).collect()
-    }
+/// Returns all state directories as defined by `StateDirectory` in the unit file, keeping empty segments.
+///
+/// This behaves like [`state_dirs`], but empty segments, such as from a leading or doubled colon, are returned as
+/// empty paths rather than being dropped. This is useful for diagnostics that need to detect malformed values.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("STATE_DIRECTORY", ":/var/lib/foo");
+/// let state_dirs = systemd_directories::state_dirs_preserving_empty();
+/// assert_eq!(state_dirs.len(), 2);
+/// ```
+#[cfg(feature = "state")]
+pub fn state_dirs_preserving_empty() -> Vec<PathBuf> {
+    ColonSeparatedPaths::from_env_key("STATE_DIRECTORY")
+        .segments()
+        .map(PathBuf::from)
+        .collect()
+}
 
-    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file.
-    ///
-    /// If the environment variable `RUNTIME_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
-    /// If it was set, it returns the first path in the colon-separated list.
-    /// To get all paths, use [`Self::runtime_dirs`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let runtime_dir = dirs.runtime_dir();
-    pub fn runtime_dir(&self) -> Option<&Path> {
+/// Returns all cache directories as defined by `CacheDirectory` in the unit file, keeping empty segments.
+///
+/// This behaves like [`cache_dirs`], but empty segments, such as from a leading or doubled colon, are returned as
+/// empty paths rather than being dropped. This is useful for diagnostics that need to detect malformed values.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CACHE_DIRECTORY", ":/var/cache/foo");
+/// let cache_dirs = systemd_directories::cache_dirs_preserving_empty();
+/// assert_eq!(cache_dirs.len(), 2);
+/// ```
+#[cfg(feature = "cache")]
+pub fn cache_dirs_preserving_empty() -> Vec<PathBuf> {
+    ColonSeparatedPaths::from_env_key("CACHE_DIRECTORY")
+        .segments()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Returns all logs directories as defined by `LogsDirectory` in the unit file, keeping empty segments.
+///
+/// This behaves like [`logs_dirs`], but empty segments, such as from a leading or doubled colon, are returned as
+/// empty paths rather than being dropped. This is useful for diagnostics that need to detect malformed values.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("LOGS_DIRECTORY", ":/var/log/foo");
+/// let logs_dirs = systemd_directories::logs_dirs_preserving_empty();
+/// assert_eq!(logs_dirs.len(), 2);
+/// ```
+#[cfg(feature = "logs")]
+pub fn logs_dirs_preserving_empty() -> Vec<PathBuf> {
+    ColonSeparatedPaths::from_env_key("LOGS_DIRECTORY")
+        .segments()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file, keeping empty segments.
+///
+/// This behaves like [`config_dirs`], but empty segments, such as from a leading or doubled colon, are returned as
+/// empty paths rather than being dropped. This is useful for diagnostics that need to detect malformed values.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CONFIGURATION_DIRECTORY", ":/etc/foo");
+/// let config_dirs = systemd_directories::config_dirs_preserving_empty();
+/// assert_eq!(config_dirs.len(), 2);
+/// ```
+#[cfg(feature = "config")]
+pub fn config_dirs_preserving_empty() -> Vec<PathBuf> {
+    ColonSeparatedPaths::from_env_key("CONFIGURATION_DIRECTORY")
+        .segments()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file as an [`OsString`].
+///
+/// This behaves like [`runtime_dir`], but reads the variable with [`env::var_os`], so paths that are not valid UTF-8
+/// are returned as-is. Empty segments, such as from a leading colon, are skipped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("RUNTIME_DIRECTORY", ":/run/foo:/run/bar");
+/// let runtime_dir = systemd_directories::runtime_dir_os();
+/// assert_eq!(runtime_dir.as_deref(), Some("/run/foo".as_ref()));
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dir_os() -> Option<OsString> {
+    first_segment_os(&env::var_os("RUNTIME_DIRECTORY")?)
+}
+
+/// Returns the first state directory as defined by `StateDirectory` in the unit file as an [`OsString`].
+///
+/// This behaves like [`state_dir`], but reads the variable with [`env::var_os`], so paths that are not valid UTF-8
+/// are returned as-is. Empty segments, such as from a leading colon, are skipped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("STATE_DIRECTORY", ":/var/lib/foo:/var/lib/bar");
+/// let state_dir = systemd_directories::state_dir_os();
+/// assert_eq!(state_dir.as_deref(), Some("/var/lib/foo".as_ref()));
+/// ```
+#[cfg(feature = "state")]
+pub fn state_dir_os() -> Option<OsString> {
+    first_segment_os(&env::var_os("STATE_DIRECTORY")?)
+}
+
+/// Returns the first cache directory as defined by `CacheDirectory` in the unit file as an [`OsString`].
+///
+/// This behaves like [`cache_dir`], but reads the variable with [`env::var_os`], so paths that are not valid UTF-8
+/// are returned as-is. Empty segments, such as from a leading colon, are skipped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CACHE_DIRECTORY", ":/var/cache/foo:/var/cache/bar");
+/// let cache_dir = systemd_directories::cache_dir_os();
+/// assert_eq!(cache_dir.as_deref(), Some("/var/cache/foo".as_ref()));
+/// ```
+#[cfg(feature = "cache")]
+pub fn cache_dir_os() -> Option<OsString> {
+    first_segment_os(&env::var_os("CACHE_DIRECTORY")?)
+}
+
+/// Returns the first logs directory as defined by `LogsDirectory` in the unit file as an [`OsString`].
+///
+/// This behaves like [`logs_dir`], but reads the variable with [`env::var_os`], so paths that are not valid UTF-8
+/// are returned as-is. Empty segments, such as from a leading colon, are skipped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("LOGS_DIRECTORY", ":/var/log/foo:/var/log/bar");
+/// let logs_dir = systemd_directories::logs_dir_os();
+/// assert_eq!(logs_dir.as_deref(), Some("/var/log/foo".as_ref()));
+/// ```
+#[cfg(feature = "logs")]
+pub fn logs_dir_os() -> Option<OsString> {
+    first_segment_os(&env::var_os("LOGS_DIRECTORY")?)
+}
+
+/// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file as an [`OsString`].
+///
+/// This behaves like [`config_dir`], but reads the variable with [`env::var_os`], so paths that are not valid UTF-8
+/// are returned as-is. Empty segments, such as from a leading colon, are skipped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CONFIGURATION_DIRECTORY", ":/etc/foo:/etc/bar");
+/// let config_dir = systemd_directories::config_dir_os();
+/// assert_eq!(config_dir.as_deref(), Some("/etc/foo".as_ref()));
+/// ```
+#[cfg(feature = "config")]
+pub fn config_dir_os() -> Option<OsString> {
+    first_segment_os(&env::var_os("CONFIGURATION_DIRECTORY")?)
+}
+
+/// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file as a [`String`].
+///
+/// This behaves like [`runtime_dir_os`], but converts the path with [`Path::to_string_lossy`], so any invalid UTF-8
+/// sequences are replaced with [`char::REPLACEMENT_CHARACTER`] rather than the value being dropped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+/// let runtime_dir = systemd_directories::runtime_dir_string_lossy();
+/// assert_eq!(runtime_dir.as_deref(), Some("/run/foo"));
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dir_string_lossy() -> Option<String> {
+    runtime_dir_os().map(|p| Path::new(&p).to_string_lossy().into_owned())
+}
+
+/// Returns the first state directory as defined by `StateDirectory` in the unit file as a [`String`].
+///
+/// This behaves like [`state_dir_os`], but converts the path with [`Path::to_string_lossy`], so any invalid UTF-8
+/// sequences are replaced with [`char::REPLACEMENT_CHARACTER`] rather than the value being dropped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+/// let state_dir = systemd_directories::state_dir_string_lossy();
+/// assert_eq!(state_dir.as_deref(), Some("/var/lib/foo"));
+/// ```
+#[cfg(feature = "state")]
+pub fn state_dir_string_lossy() -> Option<String> {
+    state_dir_os().map(|p| Path::new(&p).to_string_lossy().into_owned())
+}
+
+/// Returns the first cache directory as defined by `CacheDirectory` in the unit file as a [`String`].
+///
+/// This behaves like [`cache_dir_os`], but converts the path with [`Path::to_string_lossy`], so any invalid UTF-8
+/// sequences are replaced with [`char::REPLACEMENT_CHARACTER`] rather than the value being dropped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CACHE_DIRECTORY", "/var/cache/foo");
+/// let cache_dir = systemd_directories::cache_dir_string_lossy();
+/// assert_eq!(cache_dir.as_deref(), Some("/var/cache/foo"));
+/// ```
+#[cfg(feature = "cache")]
+pub fn cache_dir_string_lossy() -> Option<String> {
+    cache_dir_os().map(|p| Path::new(&p).to_string_lossy().into_owned())
+}
+
+/// Returns the first logs directory as defined by `LogsDirectory` in the unit file as a [`String`].
+///
+/// This behaves like [`logs_dir_os`], but converts the path with [`Path::to_string_lossy`], so any invalid UTF-8
+/// sequences are replaced with [`char::REPLACEMENT_CHARACTER`] rather than the value being dropped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("LOGS_DIRECTORY", "/var/log/foo");
+/// let logs_dir = systemd_directories::logs_dir_string_lossy();
+/// assert_eq!(logs_dir.as_deref(), Some("/var/log/foo"));
+/// ```
+#[cfg(feature = "logs")]
+pub fn logs_dir_string_lossy() -> Option<String> {
+    logs_dir_os().map(|p| Path::new(&p).to_string_lossy().into_owned())
+}
+
+/// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file as a [`String`].
+///
+/// This behaves like [`config_dir_os`], but converts the path with [`Path::to_string_lossy`], so any invalid UTF-8
+/// sequences are replaced with [`char::REPLACEMENT_CHARACTER`] rather than the value being dropped.
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+/// let config_dir = systemd_directories::config_dir_string_lossy();
+/// assert_eq!(config_dir.as_deref(), Some("/etc/foo"));
+/// ```
+#[cfg(feature = "config")]
+pub fn config_dir_string_lossy() -> Option<String> {
+    config_dir_os().map(|p| Path::new(&p).to_string_lossy().into_owned())
+}
+
+/// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file as a `file://` URL.
+///
+/// The path is percent-encoded following RFC 8089, so spaces, non-ASCII characters, and bytes that are not valid UTF-8
+/// are encoded. If the environment variable `RUNTIME_DIRECTORY` is not set or its first path is not absolute, it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("RUNTIME_DIRECTORY", "/run/my app");
+/// let runtime_dir = systemd_directories::runtime_dir_url();
+/// assert_eq!(runtime_dir.as_deref(), Some("file:///run/my%20app"));
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dir_url() -> Option<String> {
+    file_url(Path::new(&runtime_dir_os()?))
+}
+
+/// Returns the first state directory as defined by `StateDirectory` in the unit file as a `file://` URL.
+///
+/// The path is percent-encoded following RFC 8089, so spaces, non-ASCII characters, and bytes that are not valid UTF-8
+/// are encoded. If the environment variable `STATE_DIRECTORY` is not set or its first path is not absolute, it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("STATE_DIRECTORY", "/var/lib/my app");
+/// let state_dir = systemd_directories::state_dir_url();
+/// assert_eq!(state_dir.as_deref(), Some("file:///var/lib/my%20app"));
+/// ```
+#[cfg(feature = "state")]
+pub fn state_dir_url() -> Option<String> {
+    file_url(Path::new(&state_dir_os()?))
+}
+
+/// Returns the first cache directory as defined by `CacheDirectory` in the unit file as a `file://` URL.
+///
+/// The path is percent-encoded following RFC 8089, so spaces, non-ASCII characters, and bytes that are not valid UTF-8
+/// are encoded. If the environment variable `CACHE_DIRECTORY` is not set or its first path is not absolute, it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CACHE_DIRECTORY", "/var/cache/my app");
+/// let cache_dir = systemd_directories::cache_dir_url();
+/// assert_eq!(cache_dir.as_deref(), Some("file:///var/cache/my%20app"));
+/// ```
+#[cfg(feature = "cache")]
+pub fn cache_dir_url() -> Option<String> {
+    file_url(Path::new(&cache_dir_os()?))
+}
+
+/// Returns the first logs directory as defined by `LogsDirectory` in the unit file as a `file://` URL.
+///
+/// The path is percent-encoded following RFC 8089, so spaces, non-ASCII characters, and bytes that are not valid UTF-8
+/// are encoded. If the environment variable `LOGS_DIRECTORY` is not set or its first path is not absolute, it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("LOGS_DIRECTORY", "/var/log/my app");
+/// let logs_dir = systemd_directories::logs_dir_url();
+/// assert_eq!(logs_dir.as_deref(), Some("file:///var/log/my%20app"));
+/// ```
+#[cfg(feature = "logs")]
+pub fn logs_dir_url() -> Option<String> {
+    file_url(Path::new(&logs_dir_os()?))
+}
+
+/// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file as a `file://` URL.
+///
+/// The path is percent-encoded following RFC 8089, so spaces, non-ASCII characters, and bytes that are not valid UTF-8
+/// are encoded. If the environment variable `CONFIGURATION_DIRECTORY` is not set or its first path is not absolute, it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CONFIGURATION_DIRECTORY", "/etc/my app");
+/// let config_dir = systemd_directories::config_dir_url();
+/// assert_eq!(config_dir.as_deref(), Some("file:///etc/my%20app"));
+/// ```
+#[cfg(feature = "config")]
+pub fn config_dir_url() -> Option<String> {
+    file_url(Path::new(&config_dir_os()?))
+}
+
+/// Returns the access mode of the runtime directories as defined by `RuntimeDirectoryMode` in the unit file.
+///
+/// systemd does not export this setting itself, but some setups propagate it in the environment variable
+/// `RUNTIME_DIRECTORY_MODE` as an octal string, such as `0700`. If the variable is not set or is not a valid octal mode,
+/// it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("RUNTIME_DIRECTORY_MODE", "0750");
+/// let mode = systemd_directories::runtime_dir_mode();
+/// assert_eq!(mode, Some(0o750));
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dir_mode() -> Option<u32> {
+    parse_mode(&env::var("RUNTIME_DIRECTORY_MODE").ok()?)
+}
+
+/// Returns the access mode of the state directories as defined by `StateDirectoryMode` in the unit file.
+///
+/// systemd does not export this setting itself, but some setups propagate it in the environment variable
+/// `STATE_DIRECTORY_MODE` as an octal string, such as `0700`. If the variable is not set or is not a valid octal mode,
+/// it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("STATE_DIRECTORY_MODE", "0750");
+/// let mode = systemd_directories::state_dir_mode();
+/// assert_eq!(mode, Some(0o750));
+/// ```
+#[cfg(feature = "state")]
+pub fn state_dir_mode() -> Option<u32> {
+    parse_mode(&env::var("STATE_DIRECTORY_MODE").ok()?)
+}
+
+/// Returns the access mode of the cache directories as defined by `CacheDirectoryMode` in the unit file.
+///
+/// systemd does not export this setting itself, but some setups propagate it in the environment variable
+/// `CACHE_DIRECTORY_MODE` as an octal string, such as `0700`. If the variable is not set or is not a valid octal mode,
+/// it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CACHE_DIRECTORY_MODE", "0750");
+/// let mode = systemd_directories::cache_dir_mode();
+/// assert_eq!(mode, Some(0o750));
+/// ```
+#[cfg(feature = "cache")]
+pub fn cache_dir_mode() -> Option<u32> {
+    parse_mode(&env::var("CACHE_DIRECTORY_MODE").ok()?)
+}
+
+/// Returns the access mode of the logs directories as defined by `LogsDirectoryMode` in the unit file.
+///
+/// systemd does not export this setting itself, but some setups propagate it in the environment variable
+/// `LOGS_DIRECTORY_MODE` as an octal string, such as `0700`. If the variable is not set or is not a valid octal mode,
+/// it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("LOGS_DIRECTORY_MODE", "0750");
+/// let mode = systemd_directories::logs_dir_mode();
+/// assert_eq!(mode, Some(0o750));
+/// ```
+#[cfg(feature = "logs")]
+pub fn logs_dir_mode() -> Option<u32> {
+    parse_mode(&env::var("LOGS_DIRECTORY_MODE").ok()?)
+}
+
+/// Returns the access mode of the configuration directories as defined by `ConfigurationDirectoryMode` in the unit file.
+///
+/// systemd does not export this setting itself, but some setups propagate it in the environment variable
+/// `CONFIGURATION_DIRECTORY_MODE` as an octal string, such as `0700`. If the variable is not set or is not a valid octal mode,
+/// it returns [`None`].
+///
+/// # Examples
+/// ```
+/// # use std::env;
+/// # env::set_var("CONFIGURATION_DIRECTORY_MODE", "0750");
+/// let mode = systemd_directories::config_dir_mode();
+/// assert_eq!(mode, Some(0o750));
+/// ```
+#[cfg(feature = "config")]
+pub fn config_dir_mode() -> Option<u32> {
+    parse_mode(&env::var("CONFIGURATION_DIRECTORY_MODE").ok()?)
+}
+
+/// The access modes of each kind of directory, as returned by [`SystemdDirs::modes`].
+///
+/// A mode is [`None`] if its variable is not set or is not a valid octal mode, or if the crate feature for its kind is
+/// disabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DirModes {
+    /// The mode as defined by `RuntimeDirectoryMode` in the unit file.
+    runtime: Option<u32>,
+
+    /// The mode as defined by `StateDirectoryMode` in the unit file.
+    state: Option<u32>,
+
+    /// The mode as defined by `CacheDirectoryMode` in the unit file.
+    cache: Option<u32>,
+
+    /// The mode as defined by `LogsDirectoryMode` in the unit file.
+    logs: Option<u32>,
+
+    /// The mode as defined by `ConfigurationDirectoryMode` in the unit file.
+    config: Option<u32>,
+}
+
+impl DirModes {
+    /// Returns the access mode of the directories of `kind`.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::DirKind;
+    /// # std::env::set_var("STATE_DIRECTORY_MODE", "0750");
+    /// let modes = systemd_directories::SystemdDirs::new().modes();
+    /// assert_eq!(modes.get(DirKind::State), Some(0o750));
+    /// ```
+    pub fn get(&self, kind: DirKind) -> Option<u32> {
+        match kind {
+            DirKind::Runtime => self.runtime,
+            DirKind::State => self.state,
+            DirKind::Cache => self.cache,
+            DirKind::Logs => self.logs,
+            DirKind::Config => self.config,
+        }
+    }
+}
+
+/// An owned snapshot of all directories, as returned by [`all_dirs`].
+///
+/// Unlike [`SystemdDirs`], whose accessors borrow from the snapshot, this is a plain struct of owned paths that can be
+/// moved or shared across threads freely.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllDirs {
+    /// All runtime directories as defined by `RuntimeDirectory` in the unit file.
+    #[cfg(feature = "runtime")]
+    pub runtime_dirs: Vec<PathBuf>,
+
+    /// All state directories as defined by `StateDirectory` in the unit file.
+    #[cfg(feature = "state")]
+    pub state_dirs: Vec<PathBuf>,
+
+    /// All cache directories as defined by `CacheDirectory` in the unit file.
+    #[cfg(feature = "cache")]
+    pub cache_dirs: Vec<PathBuf>,
+
+    /// All logs directories as defined by `LogsDirectory` in the unit file.
+    #[cfg(feature = "logs")]
+    pub logs_dirs: Vec<PathBuf>,
+
+    /// All configuration directories as defined by `ConfigurationDirectory` in the unit file.
+    #[cfg(feature = "config")]
+    pub config_dirs: Vec<PathBuf>,
+}
+
+/// Returns all directories of all kinds as an owned snapshot.
+///
+/// This is equivalent to calling each of the plural functions, like [`runtime_dirs`], in a single call.
+///
+/// # Examples
+/// ```
+/// let dirs = systemd_directories::all_dirs();
+/// std::thread::spawn(move || {
+///     println!("runtime dirs: {:?}", dirs.runtime_dirs);
+/// });
+/// ```
+pub fn all_dirs() -> AllDirs {
+    AllDirs {
+        #[cfg(feature = "runtime")]
+        runtime_dirs: runtime_dirs(),
+        #[cfg(feature = "state")]
+        state_dirs: state_dirs(),
+        #[cfg(feature = "cache")]
+        cache_dirs: cache_dirs(),
+        #[cfg(feature = "logs")]
+        logs_dirs: logs_dirs(),
+        #[cfg(feature = "config")]
+        config_dirs: config_dirs(),
+    }
+}
+
+/// Returns a shared snapshot of the environment, taken the first time this function is called.
+///
+/// This avoids reading and parsing the environment on every call for the common case where it never changes after
+/// startup. The snapshot is taken once with [`SystemdDirs::new`] and is never refreshed, so later changes to the
+/// environment are **not** observed by this function. Use [`SystemdDirs::new`] to take a fresh snapshot.
+///
+/// # Examples
+/// ```
+/// if let Some(runtime_dir) = systemd_directories::cached().runtime_dir() {
+///     println!("runtime directory: {}", runtime_dir.display());
+/// }
+/// ```
+pub fn cached() -> &'static SystemdDirs {
+    static CACHED: OnceLock<SystemdDirs> = OnceLock::new();
+    CACHED.get_or_init(SystemdDirs::new)
+}
+
+/// Returns the major version of systemd, such as `255`, or [`None`] if the system was not booted with systemd or the
+/// version cannot be determined.
+///
+/// Like `sd_booted(3)`, the system is considered booted with systemd if `/run/systemd/system` is a directory, which
+/// excludes most containers. The version is then parsed from the output of `systemctl --version`, which does not
+/// require any privileges. This is the version of the installed `systemctl`, which may be newer than the running
+/// service manager if systemd was upgraded without a reboot. It returns [`None`] when `systemctl` is not in `PATH` or
+/// its output cannot be parsed.
+///
+/// This function is only available on Linux.
+///
+/// # Examples
+/// ```
+/// if systemd_directories::systemd_version().is_some_and(|version| version >= 247) {
+///     // --snip--
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub fn systemd_version() -> Option<u32> {
+    if !Path::new("/run/systemd/system").is_dir() {
+        return None;
+    }
+
+    let output = process::Command::new("systemctl")
+        .arg("--version")
+        .stdin(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_systemd_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The preservation behavior of runtime directories as defined by `RuntimeDirectoryPreserve` in the unit file.
+#[cfg(feature = "runtime")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuntimeDirectoryPreserve {
+    /// The runtime directories are removed when the service stops.
+    No,
+
+    /// The runtime directories are preserved when the service stops.
+    Yes,
+
+    /// The runtime directories are preserved when the service restarts, but removed when it stops.
+    Restart,
+}
+
+/// Returns the preservation behavior of the runtime directories as defined by `RuntimeDirectoryPreserve` in the unit
+/// file.
+///
+/// systemd does not export this setting itself, but some setups propagate it in the environment variable
+/// `RUNTIME_DIRECTORY_PRESERVE`. The value is parsed like systemd parses booleans, case-insensitively:
+/// `yes`, `y`, `true`, `t`, `on`, and `1` map to [`RuntimeDirectoryPreserve::Yes`], while `no`, `n`, `false`, `f`,
+/// `off`, and `0` map to [`RuntimeDirectoryPreserve::No`]. Additionally, `restart` maps to
+/// [`RuntimeDirectoryPreserve::Restart`].
+///
+/// If the variable is not set or has any other value, it returns [`None`].
+///
+/// # Examples
+/// ```
+/// use systemd_directories::RuntimeDirectoryPreserve;
+/// # std::env::set_var("RUNTIME_DIRECTORY_PRESERVE", "restart");
+/// if systemd_directories::runtime_dir_preserve() == Some(RuntimeDirectoryPreserve::Restart) {
+///     // --snip--
+/// }
+/// ```
+#[cfg(feature = "runtime")]
+pub fn runtime_dir_preserve() -> Option<RuntimeDirectoryPreserve> {
+    parse_runtime_dir_preserve(&env::var(RUNTIME_DIRECTORY_PRESERVE).ok()?)
+}
+
+/// Parses the value of `RUNTIME_DIRECTORY_PRESERVE` as documented on [`runtime_dir_preserve`].
+#[cfg(feature = "runtime")]
+fn parse_runtime_dir_preserve(value: &str) -> Option<RuntimeDirectoryPreserve> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "y" | "true" | "t" | "on" | "1" => Some(RuntimeDirectoryPreserve::Yes),
+        "no" | "n" | "false" | "f" | "off" | "0" => Some(RuntimeDirectoryPreserve::No),
+        "restart" => Some(RuntimeDirectoryPreserve::Restart),
+        _ => None,
+    }
+}
+
+/// Parses a colon-separated list of paths, such as the value of `RUNTIME_DIRECTORY`, the same way the rest of the
+/// crate does.
+///
+/// Empty segments, such as from a leading, trailing, or doubled colon, are skipped, and every other segment is kept
+/// exactly, without trimming or unquoting. On Unix, segments that are not valid UTF-8 are preserved byte for byte; on
+/// other platforms, a value that is not valid UTF-8 has no paths. This never panics for any input, which makes it
+/// suitable as a fuzzing target.
+///
+/// # Examples
+/// ```
+/// use std::ffi::OsStr;
+/// use std::path::PathBuf;
+/// let paths = systemd_directories::parse_colon_paths(OsStr::new("/run/foo::/run/bar"));
+/// assert_eq!(paths, vec![PathBuf::from("/run/foo"), PathBuf::from("/run/bar")]);
+/// ```
+pub fn parse_colon_paths(value: &OsStr) -> Vec<PathBuf> {
+    split_colons_os(value)
+        .filter(|segment| !segment.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Expands the systemd specifiers in `template`, such as `%t/myapp`, using the base directories of `dirs`.
+///
+/// The base directory of a kind is the fixed root systemd creates its directories under, rather than anything derived
+/// from the directory names, so nested directives such as `RuntimeDirectory=myapp/sub` expand correctly. If the first
+/// directory of the kind in `dirs` is under the root of the user manager, which is named by
+/// [`DirKind::user_base_env`], that root is used; otherwise, the root of the system manager is used, as returned by
+/// [`DirKind::system_base`]. For example, with `RUNTIME_DIRECTORY=/run/myapp/sub`, `%t` expands to `/run`. The
+/// supported specifiers are:
+///
+/// | Specifier | Meaning                      | Kind                 |
+/// |-----------|------------------------------|----------------------|
+/// | `%t`      | Runtime directory root       | [`DirKind::Runtime`] |
+/// | `%S`      | State directory root         | [`DirKind::State`]   |
+/// | `%C`      | Cache directory root         | [`DirKind::Cache`]   |
+/// | `%L`      | Logs directory root          | [`DirKind::Logs`]    |
+/// | `%E`      | Configuration directory root | [`DirKind::Config`]  |
+/// | `%%`      | A literal `%`                |                      |
+///
+/// Returns [`None`] if `template` contains any other specifier, ends in a lone `%`, or uses a specifier whose kind has
+/// no directories in `dirs`.
+///
+/// # Examples
+/// ```
+/// use std::path::PathBuf;
+/// use systemd_directories::SystemdDirs;
+/// # std::env::set_var("RUNTIME_DIRECTORY", "/run/myapp");
+/// let dirs = SystemdDirs::new();
+/// let socket = systemd_directories::resolve_specifiers("%t/myapp/app.sock", &dirs);
+/// assert_eq!(socket, Some(PathBuf::from("/run/myapp/app.sock")));
+/// ```
+pub fn resolve_specifiers(template: &str, dirs: &SystemdDirs) -> Option<PathBuf> {
+    let mut resolved = OsString::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            resolved.push(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+
+        let kind = match chars.next()? {
+            '%' => {
+                resolved.push("%");
+                continue;
+            }
+            't' => DirKind::Runtime,
+            'S' => DirKind::State,
+            'C' => DirKind::Cache,
+            'L' => DirKind::Logs,
+            'E' => DirKind::Config,
+            _ => return None,
+        };
+
+        let dir = dirs.get(kind).first()?;
+        match kind.xdg_base() {
+            Some(base) if dir.starts_with(&base) => resolved.push(base),
+            _ => resolved.push(kind.system_base()),
+        }
+    }
+
+    Some(PathBuf::from(resolved))
+}
+
+/// The kinds of directories systemd can set up for a unit.
+///
+/// Every kind is always available, but if the crate feature for a kind is disabled, its environment variable is never
+/// read and a [`SystemdDirs`] snapshot never has directories of that kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DirKind {
+    /// A runtime directory as defined by `RuntimeDirectory` in the unit file.
+    Runtime,
+
+    /// A state directory as defined by `StateDirectory` in the unit file.
+    State,
+
+    /// A cache directory as defined by `CacheDirectory` in the unit file.
+    Cache,
+
+    /// A logs directory as defined by `LogsDirectory` in the unit file.
+    Logs,
+
+    /// A configuration directory as defined by `ConfigurationDirectory` in the unit file.
+    Config,
+}
+
+impl DirKind {
+    /// All kinds of directories, in the order they are documented in systemd.exec(5).
+    pub const ALL: [DirKind; 5] = [
+        DirKind::Runtime,
+        DirKind::State,
+        DirKind::Cache,
+        DirKind::Logs,
+        DirKind::Config,
+    ];
+
+    /// Returns the name of the environment variable systemd sets for this kind, such as `RUNTIME_DIRECTORY`.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::DirKind;
+    /// assert_eq!(DirKind::Config.env_key(), "CONFIGURATION_DIRECTORY");
+    /// ```
+    pub fn env_key(&self) -> &'static str {
+        match self {
+            DirKind::Runtime => "RUNTIME_DIRECTORY",
+            DirKind::State => "STATE_DIRECTORY",
+            DirKind::Cache => "CACHE_DIRECTORY",
+            DirKind::Logs => "LOGS_DIRECTORY",
+            DirKind::Config => "CONFIGURATION_DIRECTORY",
+        }
+    }
+
+    /// Returns the base directory systemd uses for this kind when running as the system manager, such as `/run`.
+    ///
+    /// This returns a string rather than a [`Path`] so that it can be a `const fn`. Use [`Path::new`] to convert it.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::DirKind;
+    /// assert_eq!(DirKind::State.system_base(), "/var/lib");
+    /// ```
+    pub const fn system_base(&self) -> &'static str {
+        match self {
+            DirKind::Runtime => "/run",
+            DirKind::State => "/var/lib",
+            DirKind::Cache => "/var/cache",
+            DirKind::Logs => "/var/log",
+            DirKind::Config => "/etc",
+        }
+    }
+
+    /// Returns the name of the XDG Base Directory Specification variable systemd uses for this kind when running as the
+    /// user manager, such as `XDG_RUNTIME_DIR`.
+    ///
+    /// Logs directories have no variable of their own and live under `log` in the state directory, so
+    /// [`DirKind::Logs`] returns `XDG_STATE_HOME`.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::DirKind;
+    /// assert_eq!(DirKind::Config.user_base_env(), "XDG_CONFIG_HOME");
+    /// ```
+    pub const fn user_base_env(&self) -> &'static str {
+        match self {
+            DirKind::Runtime => "XDG_RUNTIME_DIR",
+            DirKind::State | DirKind::Logs => "XDG_STATE_HOME",
+            DirKind::Cache => "XDG_CACHE_HOME",
+            DirKind::Config => "XDG_CONFIG_HOME",
+        }
+    }
+
+    /// Returns the base directory systemd uses for this kind when running as the user manager, following the XDG Base
+    /// Directory Specification.
+    ///
+    /// The base is read from the current environment, falling back to the specification's defaults under `HOME`.
+    /// Returns [`None`] if it cannot be determined, such as if `XDG_RUNTIME_DIR` is unset.
+    fn xdg_base(&self) -> Option<PathBuf> {
+        let home = |default: &str| env::var_os("HOME").map(|home| Path::new(&home).join(default));
+        let var = |key: &str| {
+            env::var_os(key)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        match self {
+            DirKind::Runtime => var(self.user_base_env()),
+            DirKind::State => var(self.user_base_env()).or_else(|| home(".local/state")),
+            DirKind::Cache => var(self.user_base_env()).or_else(|| home(".cache")),
+            DirKind::Logs => DirKind::State.xdg_base().map(|state| state.join("log")),
+            DirKind::Config => var(self.user_base_env()).or_else(|| home(".config")),
+        }
+    }
+
+    /// Returns whether the crate feature for this kind is enabled.
+    fn is_enabled(&self) -> bool {
+        match self {
+            DirKind::Runtime => cfg!(feature = "runtime"),
+            DirKind::State => cfg!(feature = "state"),
+            DirKind::Cache => cfg!(feature = "cache"),
+            DirKind::Logs => cfg!(feature = "logs"),
+            DirKind::Config => cfg!(feature = "config"),
+        }
+    }
+
+    /// Returns the short lowercase name of the kind.
+    fn name(&self) -> &'static str {
+        match self {
+            DirKind::Runtime => "runtime",
+            DirKind::State => "state",
+            DirKind::Cache => "cache",
+            DirKind::Logs => "logs",
+            DirKind::Config => "config",
+        }
+    }
+}
+
+/// Formats the kind as its short lowercase name: `runtime`, `state`, `cache`, `logs`, or `config`.
+impl fmt::Display for DirKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What an application intends to store in a directory, mapping to the [`DirKind`] suited for it.
+///
+/// This is a semantic layer over [`DirKind`] that makes the intent clear at call sites. See
+/// [`SystemdDirs::dir_for_purpose`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Purpose {
+    /// Temporary files, such as sockets and PID files, that do not outlive the service. Maps to [`DirKind::Runtime`].
+    TemporaryFiles,
+
+    /// Data that must persist across restarts and reboots, such as databases. Maps to [`DirKind::State`].
+    PersistentData,
+
+    /// Data that can be regenerated if lost, such as caches of computed results. Maps to [`DirKind::Cache`].
+    RegenerableData,
+
+    /// Log files. Maps to [`DirKind::Logs`].
+    Logs,
+
+    /// Configuration files. Maps to [`DirKind::Config`].
+    Configuration,
+}
+
+impl Purpose {
+    /// Returns the kind of directory suited for the purpose.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, Purpose};
+    /// assert_eq!(Purpose::PersistentData.kind(), DirKind::State);
+    /// ```
+    pub fn kind(self) -> DirKind {
+        match self {
+            Purpose::TemporaryFiles => DirKind::Runtime,
+            Purpose::PersistentData => DirKind::State,
+            Purpose::RegenerableData => DirKind::Cache,
+            Purpose::Logs => DirKind::Logs,
+            Purpose::Configuration => DirKind::Config,
+        }
+    }
+}
+
+/// Whether the contents of a directory survive a restart or stop of the service, as documented in systemd.exec(5).
+///
+/// See [`SystemdDirs::persistence`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Persistence {
+    /// The directory is removed when the service stops or restarts.
+    Ephemeral,
+
+    /// The directory is kept when the service restarts, but removed when it stops.
+    UntilStop,
+
+    /// The directory is kept when the service stops or restarts.
+    Persistent,
+}
+
+/// A view of a [`SystemdDirs`] snapshot shaped like `ProjectDirs` from the `directories` crate.
+///
+/// The accessors have the same signatures as their counterparts on `directories::ProjectDirs`, so code written against
+/// it can switch to systemd's directories without a dependency on the `directories` crate. See
+/// [`SystemdDirs::as_project_dirs_like`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectDirsLike<'a> {
+    /// The first configuration directory.
+    config_dir: &'a Path,
+
+    /// The first cache directory.
+    cache_dir: &'a Path,
+
+    /// The first state directory.
+    data_dir: &'a Path,
+
+    /// The first runtime directory, if any.
+    runtime_dir: Option<&'a Path>,
+}
+
+impl<'a> ProjectDirsLike<'a> {
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file.
+    pub fn config_dir(&self) -> &Path {
+        self.config_dir
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file.
+    pub fn cache_dir(&self) -> &Path {
+        self.cache_dir
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file.
+    ///
+    /// systemd has no separate data directory, so persistent data lives in the state directory.
+    pub fn data_dir(&self) -> &Path {
+        self.data_dir
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file, if any.
+    pub fn runtime_dir(&self) -> Option<&Path> {
+        self.runtime_dir
+    }
+}
+
+/// A single directory along with the [`DirKind`] it was configured as.
+///
+/// [`SystemdDir`] dereferences to [`Path`], so it can be used anywhere a path is expected while still letting functions
+/// require a directory that came from systemd.
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{DirKind, SystemdDir};
+/// fn open_socket(dir: &SystemdDir) {
+///     assert_eq!(dir.kind(), DirKind::Runtime);
+///     let socket = dir.join("app.sock");
+///     // --snip--
+/// }
+///
+/// if let Some(runtime_dir) = systemd_directories::SystemdDirs::new().runtime_dir_typed() {
+///     open_socket(&runtime_dir);
+/// }
+/// ```
+///
+/// It also borrows as a [`Path`], so it can look up entries in maps keyed by paths. Like its hash, its equality only
+/// depends on the path, as [`Borrow`] requires, so that a map keyed by [`SystemdDir`] can be queried with a plain
+/// [`Path`]. Compare [`SystemdDir::kind`] as well to tell directories of different kinds at the same path apart.
+#[derive(Debug, Clone)]
+pub struct SystemdDir {
+    /// The kind of the directory.
+    kind: DirKind,
+
+    /// The path of the directory.
+    path: PathBuf,
+}
+
+impl SystemdDir {
+    /// Returns a new [`SystemdDir`] of the given `kind` at `path`.
+    pub fn new(kind: DirKind, path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind,
+            path: path.into(),
+        }
+    }
+
+    /// Returns the kind of the directory.
+    pub fn kind(&self) -> DirKind {
+        self.kind
+    }
+
+    /// Returns the path of the directory.
+    pub fn as_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Consumes the [`SystemdDir`], returning the path of the directory.
+    pub fn into_path_buf(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl AsRef<Path> for SystemdDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for SystemdDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl AsRef<OsStr> for SystemdDir {
+    fn as_ref(&self) -> &OsStr {
+        self.path.as_os_str()
+    }
+}
+
+impl Borrow<Path> for SystemdDir {
+    fn borrow(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Hash for SystemdDir {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+impl PartialEq for SystemdDir {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for SystemdDir {}
+
+impl PartialEq<Path> for SystemdDir {
+    fn eq(&self, other: &Path) -> bool {
+        self.path == other
+    }
+}
+
+impl PartialEq<&Path> for SystemdDir {
+    fn eq(&self, other: &&Path) -> bool {
+        self.path == *other
+    }
+}
+
+/// The first runtime directory as defined by `RuntimeDirectory` in the unit file, marked with its kind at the type level.
+///
+/// Unlike [`SystemdDir`], which records its kind at runtime, a function taking a [`RuntimeDirectory`] cannot be handed a
+/// directory of another kind. It dereferences to [`Path`] and is as cheap to copy as a `&Path`. See
+/// [`SystemdDirs::runtime_dir_marked`].
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{RuntimeDirectory, SystemdDirs};
+/// fn open(dir: RuntimeDirectory) {
+///     let file = dir.join("app");
+///     // --snip--
+/// }
+///
+/// if let Some(runtime_dir) = SystemdDirs::new().runtime_dir_marked() {
+///     open(runtime_dir);
+/// }
+/// ```
+#[cfg(feature = "runtime")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RuntimeDirectory<'a>(&'a Path);
+
+#[cfg(feature = "runtime")]
+impl<'a> RuntimeDirectory<'a> {
+    /// Returns the path of the directory with the lifetime of the [`SystemdDirs`] it came from.
+    pub fn path(self) -> &'a Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Deref for RuntimeDirectory<'_> {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl AsRef<Path> for RuntimeDirectory<'_> {
+    fn as_ref(&self) -> &Path {
+        self.0
+    }
+}
+
+/// The first state directory as defined by `StateDirectory` in the unit file, marked with its kind at the type level.
+///
+/// Unlike [`SystemdDir`], which records its kind at runtime, a function taking a [`StateDirectory`] cannot be handed a
+/// directory of another kind. It dereferences to [`Path`] and is as cheap to copy as a `&Path`. See
+/// [`SystemdDirs::state_dir_marked`].
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{StateDirectory, SystemdDirs};
+/// fn open(dir: StateDirectory) {
+///     let file = dir.join("app");
+///     // --snip--
+/// }
+///
+/// if let Some(state_dir) = SystemdDirs::new().state_dir_marked() {
+///     open(state_dir);
+/// }
+/// ```
+#[cfg(feature = "state")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateDirectory<'a>(&'a Path);
+
+#[cfg(feature = "state")]
+impl<'a> StateDirectory<'a> {
+    /// Returns the path of the directory with the lifetime of the [`SystemdDirs`] it came from.
+    pub fn path(self) -> &'a Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "state")]
+impl Deref for StateDirectory<'_> {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "state")]
+impl AsRef<Path> for StateDirectory<'_> {
+    fn as_ref(&self) -> &Path {
+        self.0
+    }
+}
+
+/// The first cache directory as defined by `CacheDirectory` in the unit file, marked with its kind at the type level.
+///
+/// Unlike [`SystemdDir`], which records its kind at runtime, a function taking a [`CacheDirectory`] cannot be handed a
+/// directory of another kind. It dereferences to [`Path`] and is as cheap to copy as a `&Path`. See
+/// [`SystemdDirs::cache_dir_marked`].
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{CacheDirectory, SystemdDirs};
+/// fn open(dir: CacheDirectory) {
+///     let file = dir.join("app");
+///     // --snip--
+/// }
+///
+/// if let Some(cache_dir) = SystemdDirs::new().cache_dir_marked() {
+///     open(cache_dir);
+/// }
+/// ```
+#[cfg(feature = "cache")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheDirectory<'a>(&'a Path);
+
+#[cfg(feature = "cache")]
+impl<'a> CacheDirectory<'a> {
+    /// Returns the path of the directory with the lifetime of the [`SystemdDirs`] it came from.
+    pub fn path(self) -> &'a Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "cache")]
+impl Deref for CacheDirectory<'_> {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "cache")]
+impl AsRef<Path> for CacheDirectory<'_> {
+    fn as_ref(&self) -> &Path {
+        self.0
+    }
+}
+
+/// The first logs directory as defined by `LogsDirectory` in the unit file, marked with its kind at the type level.
+///
+/// Unlike [`SystemdDir`], which records its kind at runtime, a function taking a [`LogsDirectory`] cannot be handed a
+/// directory of another kind. It dereferences to [`Path`] and is as cheap to copy as a `&Path`. See
+/// [`SystemdDirs::logs_dir_marked`].
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{LogsDirectory, SystemdDirs};
+/// fn open(dir: LogsDirectory) {
+///     let file = dir.join("app");
+///     // --snip--
+/// }
+///
+/// if let Some(logs_dir) = SystemdDirs::new().logs_dir_marked() {
+///     open(logs_dir);
+/// }
+/// ```
+#[cfg(feature = "logs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LogsDirectory<'a>(&'a Path);
+
+#[cfg(feature = "logs")]
+impl<'a> LogsDirectory<'a> {
+    /// Returns the path of the directory with the lifetime of the [`SystemdDirs`] it came from.
+    pub fn path(self) -> &'a Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "logs")]
+impl Deref for LogsDirectory<'_> {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "logs")]
+impl AsRef<Path> for LogsDirectory<'_> {
+    fn as_ref(&self) -> &Path {
+        self.0
+    }
+}
+
+/// The first configuration directory as defined by `ConfigurationDirectory` in the unit file, marked with its kind at the type level.
+///
+/// Unlike [`SystemdDir`], which records its kind at runtime, a function taking a [`ConfigurationDirectory`] cannot be handed a
+/// directory of another kind. It dereferences to [`Path`] and is as cheap to copy as a `&Path`. See
+/// [`SystemdDirs::config_dir_marked`].
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{ConfigurationDirectory, SystemdDirs};
+/// fn open(dir: ConfigurationDirectory) {
+///     let file = dir.join("app");
+///     // --snip--
+/// }
+///
+/// if let Some(config_dir) = SystemdDirs::new().config_dir_marked() {
+///     open(config_dir);
+/// }
+/// ```
+#[cfg(feature = "config")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConfigurationDirectory<'a>(&'a Path);
+
+#[cfg(feature = "config")]
+impl<'a> ConfigurationDirectory<'a> {
+    /// Returns the path of the directory with the lifetime of the [`SystemdDirs`] it came from.
+    pub fn path(self) -> &'a Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "config")]
+impl Deref for ConfigurationDirectory<'_> {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0
+    }
+}
+
+#[cfg(feature = "config")]
+impl AsRef<Path> for ConfigurationDirectory<'_> {
+    fn as_ref(&self) -> &Path {
+        self.0
+    }
+}
+
+/// An error returned when the environment contains a malformed directory variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SystemdDirsError {
+    /// The variable for the kind was set but is not valid UTF-8.
+    NotUnicode(DirKind),
+
+    /// The variable for the kind was set but contains no paths, such as an empty string or only colons.
+    Empty(DirKind),
+}
+
+impl SystemdDirsError {
+    /// Returns the kind whose variable is malformed.
+    pub fn kind(&self) -> DirKind {
+        match self {
+            SystemdDirsError::NotUnicode(kind) | SystemdDirsError::Empty(kind) => *kind,
+        }
+    }
+}
+
+impl fmt::Display for SystemdDirsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SystemdDirsError::NotUnicode(kind) => {
+                write!(f, "{} is not valid UTF-8", kind.env_key())
+            }
+            SystemdDirsError::Empty(kind) => write!(f, "{} is set but empty", kind.env_key()),
+        }
+    }
+}
+
+impl Error for SystemdDirsError {}
+
+/// The reason a kind has no directories, as returned by [`SystemdDirs::try_get`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirStatus {
+    /// The variable for the kind was not set.
+    Unset,
+
+    /// The variable for the kind was set but contained no paths, such as an empty string or only colons.
+    EmptyButPresent,
+}
+
+impl fmt::Display for DirStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DirStatus::Unset => "variable is not set",
+            DirStatus::EmptyButPresent => "variable is set but contains no paths",
+        })
+    }
+}
+
+impl Error for DirStatus {}
+
+/// An error returned by [`SystemdDirs::new_canonical_lexical`] when a directory is not a clean absolute path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    /// The kind of the offending directory.
+    kind: DirKind,
+
+    /// The offending directory.
+    path: PathBuf,
+}
+
+impl PathError {
+    /// Returns the kind of the offending directory.
+    pub fn kind(&self) -> DirKind {
+        self.kind
+    }
+
+    /// Returns the offending directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} directory {} is not a clean absolute path",
+            self.kind,
+            self.path.display()
+        )
+    }
+}
+
+impl Error for PathError {}
+
+/// An error returned by [`SystemdDirs::new_validated`] when directories are missing or are not directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The directories that do not exist or cannot be accessed.
+    missing: Vec<(DirKind, PathBuf)>,
+
+    /// The paths that exist but are not directories.
+    not_directories: Vec<(DirKind, PathBuf)>,
+}
+
+impl ValidationError {
+    /// Returns the directories that do not exist or cannot be accessed, in the order of [`SystemdDirs::flat_iter`].
+    pub fn missing(&self) -> &[(DirKind, PathBuf)] {
+        &self.missing
+    }
+
+    /// Returns the paths that exist but are not directories, in the order of [`SystemdDirs::flat_iter`].
+    pub fn not_directories(&self) -> &[(DirKind, PathBuf)] {
+        &self.not_directories
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let missing = self
+            .missing
+            .iter()
+            .map(|(kind, path)| (kind, path, "is missing"));
+        let not_directories =
+            (self.not_directories.iter()).map(|(kind, path)| (kind, path, "is not a directory"));
+        for (i, (kind, path, problem)) in missing.chain(not_directories).enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+
+            write!(f, "{kind} directory {} {problem}", path.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ValidationError {}
+
+/// An error returned by [`SystemdDirs::from_json`].
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum JsonError {
+    /// The document could not be read.
+    Io(io::Error),
+
+    /// The document is not valid JSON or does not match the expected shape.
+    Invalid(serde_json::Error),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Io(e) => write!(f, "failed to read JSON: {e}"),
+            JsonError::Invalid(e) => write!(f, "invalid JSON: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Io(e) => Some(e),
+            JsonError::Invalid(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for JsonError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            JsonError::Io(e.into())
+        } else {
+            JsonError::Invalid(e)
+        }
+    }
+}
+
+/// A struct to snapshot the environment at the time of creation.
+///
+/// The [`SystemdDirs`] methods return [`Path`] objects as immutable references to the paths as available when
+/// [`Self::new`] was called. This differs from the standalone functions, which return [`PathBuf`] objects.
+/// Methods with a `_buf` suffix, such as [`Self::runtime_dir_buf`], instead return an owned [`PathBuf`] like the
+/// standalone functions.
+///
+/// Two snapshots are equal if they have the same directories of each kind in the same order, regardless of how the
+/// variables were written: `STATE_DIRECTORY=/a` and `STATE_DIRECTORY=/a:` give equal snapshots. Use
+/// [`Self::eq_ignoring_order`] to compare only the sets of directories.
+///
+/// [`SystemdDirs`] is [`Send`] and [`Sync`], so a single snapshot can be shared between threads, such as with
+/// [`Self::new_shared`].
+#[derive(Debug, Clone)]
+pub struct SystemdDirs {
+    /// All runtime directories when the struct was created.
+    #[cfg(feature = "runtime")]
+    runtime_dirs: Vec<PathBuf>,
+
+    /// All state directories when the struct was created.
+    #[cfg(feature = "state")]
+    state_dirs: Vec<PathBuf>,
+
+    /// All cache directories when the struct was created.
+    #[cfg(feature = "cache")]
+    cache_dirs: Vec<PathBuf>,
+
+    /// All logs directories when the struct was created.
+    #[cfg(feature = "logs")]
+    logs_dirs: Vec<PathBuf>,
+
+    /// All configuration directories when the struct was created.
+    #[cfg(feature = "config")]
+    config_dirs: Vec<PathBuf>,
+
+    /// The kinds whose environment variable was present when the struct was created, even if it had no paths, along
+    /// with the total and non-empty number of segments in it.
+    present: Vec<(DirKind, usize, usize)>,
+
+    /// The preservation behavior of the runtime directories from [`RUNTIME_DIRECTORY_PRESERVE`] when the struct was
+    /// created.
+    #[cfg(feature = "runtime")]
+    runtime_preserve: Option<RuntimeDirectoryPreserve>,
+}
+
+impl SystemdDirs {
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment.
+    ///
+    /// With the `metrics` feature, every snapshot also sets these gauges through the recorder installed with the
+    /// `metrics` crate:
+    ///
+    /// | Name                        | Labels | Value                                      |
+    /// |-----------------------------|--------|--------------------------------------------|
+    /// | `systemd_directories_count` | `kind` | The number of directories of the kind      |
+    /// | `systemd_directories_total` |        | The number of directories across all kinds |
+    ///
+    /// The `kind` label is the [`Display`](fmt::Display) name of a [`DirKind`], and a count is set for every kind whose
+    /// crate feature is enabled, even if it has no directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new();
+    /// ```
+    pub fn new() -> Self {
+        let snapshot = env_snapshot();
+        let dirs = Self::from_lookup(|key| snapshot.get(key).cloned());
+        #[cfg(feature = "metrics")]
+        dirs.record_metrics();
+
+        dirs
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, wrapped in an [`Arc`].
+    ///
+    /// This is a convenience for sharing one snapshot between many threads without cloning its directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_shared();
+    /// let worker = {
+    ///     let dirs = Arc::clone(&dirs);
+    ///     thread::spawn(move || dirs.runtime_dir().map(|p| p.to_path_buf()))
+    /// };
+    /// assert_eq!(worker.join().unwrap().as_deref(), dirs.runtime_dir());
+    /// ```
+    pub fn new_shared() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, failing on malformed variables.
+    ///
+    /// Unlike [`Self::new`], which silently treats malformed variables as unset, this returns an error if any
+    /// variable is set but is not valid UTF-8 or contains no paths. Unset variables are not an error.
+    ///
+    /// # Errors
+    /// Returns the [`SystemdDirsError`] for the first malformed variable, in the order of [`DirKind::ALL`]. Variables of
+    /// kinds whose crate feature is disabled are not read.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_strict()?;
+    /// # Ok::<(), systemd_directories::SystemdDirsError>(())
+    /// ```
+    pub fn new_strict() -> Result<Self, SystemdDirsError> {
+        let mut vars = HashMap::new();
+        for kind in DirKind::ALL.into_iter().filter(DirKind::is_enabled) {
+            if let Some(value) = env::var_os(kind.env_key()) {
+                let value = value
+                    .into_string()
+                    .map_err(|_| SystemdDirsError::NotUnicode(kind))?;
+
+                vars.insert(kind.env_key(), value);
+            }
+        }
+
+        #[cfg(feature = "runtime")]
+        if let Some(value) = env_lookup(RUNTIME_DIRECTORY_PRESERVE) {
+            vars.insert(RUNTIME_DIRECTORY_PRESERVE, value);
+        }
+
+        let dirs = Self::from_lookup(|key| vars.get(key).cloned());
+        match dirs.check_empty().first() {
+            Some(&kind) => Err(SystemdDirsError::Empty(kind)),
+            None => Ok(dirs),
+        }
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, failing unless every
+    /// directory exists and is a directory.
+    ///
+    /// This is useful to fail fast at startup when a unit is misconfigured, such as when a hardened unit does not let
+    /// systemd create its directories. Symbolic links are followed, so a link to a directory is valid. All directories
+    /// are checked, so the error lists every problem rather than only the first.
+    ///
+    /// # Errors
+    /// Returns a [`ValidationError`] listing the directories that are missing, cannot be accessed, or are not
+    /// directories.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_validated()?;
+    /// # Ok::<(), systemd_directories::ValidationError>(())
+    /// ```
+    pub fn new_validated() -> Result<Self, ValidationError> {
+        let dirs = Self::new();
+        let mut missing = Vec::new();
+        let mut not_directories = Vec::new();
+        for (kind, _, dir) in dirs.flat_iter() {
+            match fs::metadata(dir) {
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => not_directories.push((kind, dir.to_path_buf())),
+                Err(_) => missing.push((kind, dir.to_path_buf())),
+            }
+        }
+
+        if missing.is_empty() && not_directories.is_empty() {
+            return Ok(dirs);
+        }
+
+        Err(ValidationError {
+            missing,
+            not_directories,
+        })
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, failing unless every
+    /// directory is a clean absolute path.
+    ///
+    /// A clean absolute path starts at the root and has no `.` or `..` components, such as `/var/lib/foo`. This keeps
+    /// paths like `/var/lib/../../etc` out of the snapshot of hardened services. This is purely lexical: the
+    /// filesystem is not accessed, so symlinks are not resolved.
+    ///
+    /// # Errors
+    /// Returns a [`PathError`] for the first directory that is relative or has `.` or `..` components, in the order of
+    /// [`Self::flat_iter`].
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_canonical_lexical()?;
+    /// # Ok::<(), systemd_directories::PathError>(())
+    /// ```
+    pub fn new_canonical_lexical() -> Result<Self, PathError> {
+        let dirs = Self::new();
+        let unclean = dirs.flat_iter().find(|(_, _, dir)| {
+            let dots = |segment: &[u8]| segment == b"." || segment == b"..";
+            !dir.is_absolute()
+                || dir
+                    .as_os_str()
+                    .as_encoded_bytes()
+                    .split(|&b| b == b'/')
+                    .any(dots)
+        });
+
+        match unclean {
+            Some((kind, _, dir)) => Err(PathError {
+                kind,
+                path: dir.to_path_buf(),
+            }),
+            None => Ok(dirs),
+        }
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, unless it was inherited from
+    /// another process.
+    ///
+    /// systemd sets `LISTEN_PID` to the PID of the process meant to receive socket-activated file descriptors. A
+    /// process that re-executes or spawns children may pass its environment on to processes the directories were not
+    /// meant for. As a defensive measure, if `LISTEN_PID` is set and is not the current process's ID, including when
+    /// it is not a valid PID, the directory variables are not trusted and an empty snapshot is returned. If
+    /// `LISTEN_PID` is not set, this behaves like [`Self::new`].
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_for_current_pid();
+    /// ```
+    pub fn new_for_current_pid() -> Self {
+        match env::var_os("LISTEN_PID") {
+            Some(pid) if pid.to_str().and_then(|p| p.parse().ok()) != Some(process::id()) => {
+                Self::from_lookup(|_| None)
+            }
+            _ => Self::new(),
+        }
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, using the conventional system
+    /// directories for `service_name` for any kind without paths.
+    ///
+    /// When a service runs under the system manager, systemd places its directories under `/run`, `/var/lib`,
+    /// `/var/cache`, `/var/log`, and `/etc`. For each kind whose variable is unset or contains no paths, this
+    /// synthesizes the path `<base>/<service_name>`, such as `/var/lib/myapp`. These are conventions, not guarantees:
+    /// the synthesized directories may not exist and are not the directories systemd would use for user services.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::with_system_defaults("myapp");
+    /// let state_dir = dirs.state_dir().unwrap();
+    /// ```
+    pub fn with_system_defaults(service_name: &str) -> Self {
+        let mut dirs = Self::new();
+        for kind in DirKind::ALL {
+            if let Some(kind_dirs) = dirs.get_mut(kind).filter(|d| d.is_empty()) {
+                kind_dirs.push(Path::new(kind.system_base()).join(service_name));
+            }
+        }
+
+        dirs
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, lexically normalizing each path.
+    ///
+    /// Normalization collapses repeated separators, drops trailing separators, and removes `.` components, so
+    /// `/run/foo/`, `/run/./foo`, and `/run//foo` all become `/run/foo`. It is purely lexical: the filesystem is not
+    /// accessed, and neither `..` components nor symlinks are resolved.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_normalized();
+    /// ```
+    pub fn new_normalized() -> Self {
+        Self::new().map_dirs(normalize)
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, keeping empty segments.
+    ///
+    /// By default, empty segments, such as from a leading or doubled colon, are silently dropped. This constructor
+    /// instead keeps them as empty paths, which is useful for diagnostics that need to detect malformed values. For
+    /// example, `RUNTIME_DIRECTORY=:a::b` has the runtime directories `a` and `b` with [`Self::new`], but an empty
+    /// path, `a`, another empty path, and `b` with this constructor.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_preserving_empty();
+    /// ```
+    pub fn new_preserving_empty() -> Self {
+        Self::from_lookup_with(
+            env_lookup,
+            &ParseOptions {
+                keep_empty: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, removing surrounding quotes.
+    ///
+    /// Some deployment tooling sets the variables with quoted segments, such as `RUNTIME_DIRECTORY='/run/foo'`, which
+    /// [`Self::new`] treats as part of the path. This constructor instead removes a single matching pair of surrounding
+    /// single or double quotes from each segment. Quotes elsewhere in a segment, or unmatched quotes, are kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_unquoted();
+    /// ```
+    pub fn new_unquoted() -> Self {
+        Self::from_lookup_with(
+            env_lookup,
+            &ParseOptions {
+                unquote: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, trimming whitespace around
+    /// each path.
+    ///
+    /// A value such as `RUNTIME_DIRECTORY=" "` is almost certainly a mistake in the unit file, but [`Self::new`] parses
+    /// it byte-exactly as a directory named with a single space. This constructor removes leading and trailing
+    /// whitespace from each segment, so whitespace-only segments become empty and are skipped. This could drop or
+    /// rename a directory whose name legitimately begins or ends with whitespace.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_trimmed();
+    /// ```
+    pub fn new_trimmed() -> Self {
+        Self::from_lookup_with(
+            env_lookup,
+            &ParseOptions {
+                trim: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, treating backslashes as escape
+    /// characters.
+    ///
+    /// systemd never escapes colons in these variables, so [`Self::new`] always splits on every colon. Some wrappers
+    /// encode literal colons in paths as `\:`, so this constructor only splits on unescaped colons and replaces `\:`
+    /// with `:` and `\\` with `\`. Any other backslash is kept as is.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use systemd_directories::SystemdDirs;
+    /// # std::env::set_var("RUNTIME_DIRECTORY", r"/run/a\:b:/run/c");
+    /// let dirs = SystemdDirs::new_with_escapes();
+    /// assert_eq!(dirs.runtime_dirs(), vec![Path::new("/run/a:b"), Path::new("/run/c")]);
+    /// ```
+    pub fn new_with_escapes() -> Self {
+        Self::from_lookup_with(
+            env_lookup,
+            &ParseOptions {
+                escapes: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, splitting paths on any of
+    /// `separators`.
+    ///
+    /// systemd always separates paths with `:`, which is what [`Self::new`] uses. This constructor is for values joined
+    /// by other tooling, such as with `;`. Segments are split on any of the given characters, so `&[':', ';']` accepts
+    /// mixed separators. If `separators` is empty, each variable is a single path.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new_with_separators(&[':', ';']);
+    /// ```
+    pub fn new_with_separators(separators: &[char]) -> Self {
+        Self::from_lookup_with(
+            env_lookup,
+            &ParseOptions {
+                separators: separators.to_vec(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, reading variables whose names
+    /// start with `prefix`.
+    ///
+    /// Each kind is read from its variable prefixed with `prefix`, such as `FOO_RUNTIME_DIRECTORY` for a prefix of
+    /// `FOO_`. If the prefixed variable is unset, the unprefixed variable is used instead. This lets a wrapper around
+    /// instances of a template unit (`foo@.service`) give each instance its own directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::with_prefix("FOO_");
+    /// ```
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self::from_lookup(|key| env_lookup(&format!("{prefix}{key}")).or_else(|| env_lookup(key)))
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, reading fallback variables for
+    /// kinds whose systemd variable is unset.
+    ///
+    /// Each entry of `fallbacks` names a variable to read for a kind, such as `(DirKind::State, "APP_STATE_DIR")`, when
+    /// the kind's systemd variable is unset. The systemd variable always takes precedence when set, even if it is
+    /// empty. If a kind has several fallbacks, the first one that is set is used.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new_with_fallbacks(&[(DirKind::State, "APP_STATE_DIR")]);
+    /// ```
+    pub fn new_with_fallbacks(fallbacks: &[(DirKind, &str)]) -> Self {
+        Self::from_lookup(|key| {
+            env_lookup(key).or_else(|| {
+                fallbacks
+                    .iter()
+                    .filter(|(kind, _)| kind.env_key() == key)
+                    .find_map(|(_, fallback)| env_lookup(fallback))
+            })
+        })
+    }
+
+    /// Returns the snapshot with all runtime directories replaced by `dirs`.
+    ///
+    /// The other kinds are left unchanged. This is useful in tests or to redirect a single kind at runtime. The kind is
+    /// reported as set by [`Self::present_vars`], with [`Self::raw_segment_info`] counting the new directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new().with_runtime_dirs(vec![PathBuf::from("/tmp/runtime")]);
+    /// assert_eq!(dirs.runtime_dir(), Some(Path::new("/tmp/runtime")));
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn with_runtime_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.runtime_dirs = dirs;
+        self.replace_present(DirKind::Runtime);
+        self
+    }
+
+    /// Returns the snapshot with all state directories replaced by `dirs`.
+    ///
+    /// The other kinds are left unchanged. This is useful in tests or to redirect a single kind at runtime. The kind is
+    /// reported as set by [`Self::present_vars`], with [`Self::raw_segment_info`] counting the new directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new().with_state_dirs(vec![PathBuf::from("/tmp/state")]);
+    /// assert_eq!(dirs.state_dir(), Some(Path::new("/tmp/state")));
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn with_state_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.state_dirs = dirs;
+        self.replace_present(DirKind::State);
+        self
+    }
+
+    /// Returns the snapshot with all cache directories replaced by `dirs`.
+    ///
+    /// The other kinds are left unchanged. This is useful in tests or to redirect a single kind at runtime. The kind is
+    /// reported as set by [`Self::present_vars`], with [`Self::raw_segment_info`] counting the new directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new().with_cache_dirs(vec![PathBuf::from("/tmp/cache")]);
+    /// assert_eq!(dirs.cache_dir(), Some(Path::new("/tmp/cache")));
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn with_cache_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.cache_dirs = dirs;
+        self.replace_present(DirKind::Cache);
+        self
+    }
+
+    /// Returns the snapshot with all logs directories replaced by `dirs`.
+    ///
+    /// The other kinds are left unchanged. This is useful in tests or to redirect a single kind at runtime. The kind is
+    /// reported as set by [`Self::present_vars`], with [`Self::raw_segment_info`] counting the new directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new().with_logs_dirs(vec![PathBuf::from("/tmp/logs")]);
+    /// assert_eq!(dirs.logs_dir(), Some(Path::new("/tmp/logs")));
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn with_logs_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.logs_dirs = dirs;
+        self.replace_present(DirKind::Logs);
+        self
+    }
+
+    /// Returns the snapshot with all configuration directories replaced by `dirs`.
+    ///
+    /// The other kinds are left unchanged. This is useful in tests or to redirect a single kind at runtime. The kind is
+    /// reported as set by [`Self::present_vars`], with [`Self::raw_segment_info`] counting the new directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new().with_config_dirs(vec![PathBuf::from("/tmp/config")]);
+    /// assert_eq!(dirs.config_dir(), Some(Path::new("/tmp/config")));
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn with_config_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.config_dirs = dirs;
+        self.replace_present(DirKind::Config);
+        self
+    }
+
+    /// Records `kind` as present with one segment per directory, after its directories were replaced.
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    fn replace_present(&mut self, kind: DirKind) {
+        let dirs = self.get(kind);
+        let entry = (
+            kind,
+            dirs.len(),
+            dirs.iter()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .count(),
+        );
+        match self
+            .present
+            .binary_search_by_key(&kind, |&(present, _, _)| present)
+        {
+            Ok(index) => self.present[index] = entry,
+            Err(index) => self.present.insert(index, entry),
+        }
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the variables in the dotenv-style file at `path`.
+    ///
+    /// This is useful to simulate systemd during local development. The file is parsed as `KEY=VALUE` lines, ignoring
+    /// blank lines and lines starting with `#`. Values may be surrounded by single or double quotes. Only the five
+    /// directory variables are read from the file; all others are ignored. The process environment is neither read
+    /// nor modified.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or if a line is not of the form `KEY=VALUE`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::from_env_file(".env")?;
+    /// let runtime_dir = dirs.runtime_dir();
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_env_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let vars = parse_env_file(&fs::read_to_string(path)?)?;
+        Ok(Self::from_lookup(|key| vars.get(key).cloned()))
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with the directories in the JSON document read from `reader`.
+    ///
+    /// This is useful in environments that emulate systemd's directory semantics without setting its environment
+    /// variables, such as some container orchestrators. The document must be an object whose keys are the names of
+    /// kinds as displayed by [`DirKind`] (`runtime`, `state`, `cache`, `logs`, and `config`) and whose values are
+    /// arrays of paths. Missing keys have no directories, and empty paths are skipped like empty segments of an
+    /// environment variable. The process environment is neither read nor modified.
+    ///
+    /// This function is only available with the `serde` feature.
+    ///
+    /// # Errors
+    /// Returns [`JsonError::Io`] if `reader` fails, and [`JsonError::Invalid`] if the document is not valid JSON, has
+    /// unknown or duplicate keys, or has values that are not arrays of strings.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use systemd_directories::SystemdDirs;
+    /// let json = r#"{ "runtime": ["/run/myapp"], "state": ["/var/lib/myapp"] }"#;
+    /// let dirs = SystemdDirs::from_json(json.as_bytes())?;
+    /// assert_eq!(dirs.runtime_dir(), Some(Path::new("/run/myapp")));
+    /// # Ok::<(), systemd_directories::JsonError>(())
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn from_json(reader: impl io::Read) -> Result<Self, JsonError> {
+        let mut document: JsonDocument = serde_json::from_reader(reader)?;
+        let mut dirs = Self::from_lookup(|_| None);
+        for kind in DirKind::ALL {
+            let (Some(values), Some(kind_dirs)) = (document.take(kind), dirs.get_mut(kind)) else {
+                continue;
+            };
+
+            let total = values.len();
+            kind_dirs.extend(values.into_iter().filter(|v| !v.as_os_str().is_empty()));
+            let non_empty = kind_dirs.len();
+            dirs.present.push((kind, total, non_empty));
+        }
+
+        Ok(dirs)
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the environment of the process with ID `pid`.
+    ///
+    /// The environment is read from `/proc/<pid>/environ`, which reflects the environment the process was started
+    /// with, not any later changes it made to its own environment. This is useful for supervisors that inspect the
+    /// directories of their children. The current process's environment is neither read nor modified.
+    ///
+    /// This function is only available on Linux.
+    ///
+    /// # Errors
+    /// Returns an error if `/proc/<pid>/environ` cannot be read, such as if the process does not exist or belongs to
+    /// another user.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::from_pid(std::process::id())?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn from_pid(pid: u32) -> io::Result<Self> {
+        let vars = parse_environ(&fs::read(format!("/proc/{pid}/environ"))?);
+        Ok(Self::from_lookup(|key| vars.get(key).cloned()))
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the environment of the process with ID `pid`, without
+    /// blocking the async runtime.
+    ///
+    /// This behaves like [`Self::from_pid`], but reads `/proc/<pid>/environ` with [`tokio::fs::read`], so supervisors
+    /// running on tokio can inspect many processes concurrently.
+    ///
+    /// This function is only available on Linux with the `tokio` feature.
+    ///
+    /// # Errors
+    /// Returns an error if `/proc/<pid>/environ` cannot be read, such as if the process does not exist or belongs to
+    /// another user.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let dirs = SystemdDirs::from_pid_async(std::process::id()).await?;
+    /// # Ok::<(), std::io::Error>(())
+    /// # }).unwrap();
+    /// ```
+    #[cfg(all(feature = "tokio", target_os = "linux"))]
+    pub async fn from_pid_async(pid: u32) -> io::Result<Self> {
+        let vars = parse_environ(&tokio::fs::read(format!("/proc/{pid}/environ")).await?);
+        Ok(Self::from_lookup(|key| vars.get(key).cloned()))
+    }
+
+    /// Returns a [`SystemdDirsBuilder`] to configure how the environment is read before taking a snapshot.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::builder().treat_empty_as_unset(true).build();
+    /// ```
+    pub fn builder() -> SystemdDirsBuilder {
+        SystemdDirsBuilder::default()
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with the variables returned by `lookup`.
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        Self::from_lookup_with(lookup, &ParseOptions::default())
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with the variables returned by `lookup`, parsed according to `options`.
+    fn from_lookup_with(lookup: impl Fn(&str) -> Option<String>, options: &ParseOptions) -> Self {
+        #[cfg_attr(
+            not(any(
+                feature = "runtime",
+                feature = "state",
+                feature = "cache",
+                feature = "logs",
+                feature = "config"
+            )),
+            allow(unused_variables)
+        )]
+        let dirs = |key| ColonSeparatedPaths::new(lookup(key).unwrap_or_default()).parse(options);
+
+        Self {
+            #[cfg(feature = "runtime")]
+            runtime_dirs: dirs(DirKind::Runtime.env_key()),
+            #[cfg(feature = "state")]
+            state_dirs: dirs(DirKind::State.env_key()),
+            #[cfg(feature = "cache")]
+            cache_dirs: dirs(DirKind::Cache.env_key()),
+            #[cfg(feature = "logs")]
+            logs_dirs: dirs(DirKind::Logs.env_key()),
+            #[cfg(feature = "config")]
+            config_dirs: dirs(DirKind::Config.env_key()),
+            present: DirKind::ALL
+                .into_iter()
+                .filter(|kind| kind.is_enabled())
+                .filter_map(|kind| {
+                    let value = lookup(kind.env_key())?;
+                    if options.empty_is_unset && value.is_empty() {
+                        return None;
+                    }
+
+                    let (total, non_empty) = options.segments(&value).iter().fold(
+                        (0, 0),
+                        |(total, non_empty), segment| {
+                            (total + 1, non_empty + usize::from(!segment.is_empty()))
+                        },
+                    );
+                    Some((kind, total, non_empty))
+                })
+                .collect(),
+            #[cfg(feature = "runtime")]
+            runtime_preserve: lookup(RUNTIME_DIRECTORY_PRESERVE)
+                .as_deref()
+                .and_then(parse_runtime_dir_preserve),
+        }
+    }
+
+    /// Returns the kinds whose environment variable was set but contained no paths when [`SystemdDirs`] was created.
+    ///
+    /// The crate otherwise treats a variable that is empty or only contains colons, such as `CACHE_DIRECTORY=":"`, the
+    /// same as an unset variable. This is usually a misconfiguration worth surfacing. The kinds are returned in the
+    /// order of [`DirKind::ALL`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for kind in dirs.check_empty() {
+    ///     eprintln!("warning: {} is set but empty", kind.env_key());
+    /// }
+    /// ```
+    pub fn check_empty(&self) -> Vec<DirKind> {
+        self.present
+            .iter()
+            .map(|&(kind, _, _)| kind)
+            .filter(|&kind| self.get(kind).is_empty())
+            .collect()
+    }
+
+    /// Returns the kinds whose environment variable was set when [`SystemdDirs`] was created.
+    ///
+    /// A variable counts as set even if it is empty or contains no paths, so this can differ from the kinds that have
+    /// directories. The kinds are returned in the order of [`DirKind::ALL`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for kind in dirs.present_vars() {
+    ///     println!("{} is set", kind.env_key());
+    /// }
+    /// ```
+    pub fn present_vars(&self) -> Vec<DirKind> {
+        self.present.iter().map(|&(kind, _, _)| kind).collect()
+    }
+
+    /// Returns the total and non-empty number of segments between separators in the environment variable of `kind`
+    /// when [`SystemdDirs`] was created, or [`None`] if it was unset.
+    ///
+    /// This distinguishes values that otherwise look the same: an unset variable returns [`None`], an empty one returns
+    /// `Some((1, 0))`, and `a::b` returns `Some((3, 2))`. After methods that replace directories, such as
+    /// [`Self::with_runtime_dirs`], the counts are of the new directories instead.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// if let Some((total, non_empty)) = dirs.raw_segment_info(DirKind::Runtime) {
+    ///     println!("{} has {total} segments, {non_empty} of them non-empty", DirKind::Runtime.env_key());
+    /// }
+    /// ```
+    pub fn raw_segment_info(&self, kind: DirKind) -> Option<(usize, usize)> {
+        self.present
+            .iter()
+            .find(|&&(present, _, _)| present == kind)
+            .map(|&(_, total, non_empty)| (total, non_empty))
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file.
+    ///
+    /// If the environment variable `RUNTIME_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
+    /// If it was set, it returns all paths in the colon-separated list.
+    /// To get just the first path, use [`Self::runtime_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dirs = dirs.runtime_dirs();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs(&self) -> Vec<&Path> {
+        self.as_paths(&self.runtime_dirs).collect()
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file.
+    ///
+    /// If the environment variable `RUNTIME_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
+    /// If it was set, it returns the first path in the colon-separated list.
+    /// To get all paths, use [`Self::runtime_dirs`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir();
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir(&self) -> Option<&Path> {
         self.as_paths(&self.runtime_dirs).next()
     }
 
-    /// Returns all state directories as defined by `StateDirectory` in the unit file.
-    ///
-    /// If the environment variable `STATE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
-    /// If it was set, it returns all paths in the colon-separated list.
-    /// To get just the first path, use [`Self::state_dir`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let state_dirs = dirs.state_dirs();
-    /// ```
-    pub fn state_dirs(&self) -> Vec<&Path> {
-        self.as_paths(&self.state_dirs).collect()
+    /// Returns all state directories as defined by `StateDirectory` in the unit file.
+    ///
+    /// If the environment variable `STATE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
+    /// If it was set, it returns all paths in the colon-separated list.
+    /// To get just the first path, use [`Self::state_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dirs = dirs.state_dirs();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs(&self) -> Vec<&Path> {
+        self.as_paths(&self.state_dirs).collect()
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file.
+    ///
+    /// If the environment variable `STATE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
+    /// If it was set, it returns the first path in the colon-separated list.
+    /// To get all paths, use [`Self::state_dirs`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir(&self) -> Option<&Path> {
+        self.as_paths(&self.state_dirs).next()
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file.
+    ///
+    /// If the environment variable `CACHE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
+    /// If it was set, it returns all paths in the colon-separated list.
+    /// To get just the first path, use [`Self::cache_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dirs = dirs.cache_dirs();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs(&self) -> Vec<&Path> {
+        self.as_paths(&self.cache_dirs).collect()
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file.
+    ///
+    /// If the environment variable `CACHE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
+    /// If it was set, it returns the first path in the colon-separated list.
+    /// To get all paths, use [`Self::cache_dirs`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.as_paths(&self.cache_dirs).next()
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file.
+    ///
+    /// If the environment variable `LOGS_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
+    /// If it was set, it returns all paths in the colon-separated list.
+    /// To get just the first path, use [`Self::logs_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dirs = dirs.logs_dirs();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs(&self) -> Vec<&Path> {
+        self.as_paths(&self.logs_dirs).collect()
+    }
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file.
+    ///
+    /// If the environment variable `LOGS_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
+    /// If it was set, it returns the first path in the colon-separated list.
+    /// To get all paths, use [`Self::logs_dirs`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir(&self) -> Option<&Path> {
+        self.as_paths(&self.logs_dirs).next()
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file.
+    ///
+    /// If the environment variable `CONFIGURATION_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
+    /// If it was set, it returns all paths in the colon-separated list.
+    /// To get just the first path, use [`Self::config_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dirs = dirs.config_dirs();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs(&self) -> Vec<&Path> {
+        self.as_paths(&self.config_dirs).collect()
+    }
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file.
+    ///
+    /// If the environment variable `CONFIGURATION_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
+    /// If it was set, it returns the first path in the colon-separated list.
+    /// To get all paths, use [`Self::config_dirs`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir(&self) -> Option<&Path> {
+        self.as_paths(&self.config_dirs).next()
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file that currently exist.
+    ///
+    /// This behaves like [`Self::runtime_dirs`], but only returns the paths for which [`Path::exists`] is true.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dirs = dirs.runtime_dirs_existing();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_existing(&self) -> Vec<&Path> {
+        self.as_paths(&self.runtime_dirs)
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Returns all state directories as defined by `StateDirectory` in the unit file that currently exist.
+    ///
+    /// This behaves like [`Self::state_dirs`], but only returns the paths for which [`Path::exists`] is true.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dirs = dirs.state_dirs_existing();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs_existing(&self) -> Vec<&Path> {
+        self.as_paths(&self.state_dirs)
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file that currently exist.
+    ///
+    /// This behaves like [`Self::cache_dirs`], but only returns the paths for which [`Path::exists`] is true.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dirs = dirs.cache_dirs_existing();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs_existing(&self) -> Vec<&Path> {
+        self.as_paths(&self.cache_dirs)
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file that currently exist.
+    ///
+    /// This behaves like [`Self::logs_dirs`], but only returns the paths for which [`Path::exists`] is true.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dirs = dirs.logs_dirs_existing();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs_existing(&self) -> Vec<&Path> {
+        self.as_paths(&self.logs_dirs)
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file that currently exist.
+    ///
+    /// This behaves like [`Self::config_dirs`], but only returns the paths for which [`Path::exists`] is true.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dirs = dirs.config_dirs_existing();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_existing(&self) -> Vec<&Path> {
+        self.as_paths(&self.config_dirs)
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file that are symlinks.
+    ///
+    /// systemd creates symlinks for directories configured with the `source:destination` syntax. This distinguishes
+    /// them from real directories using [`fs::symlink_metadata`], which does not follow symlinks.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Errors
+    /// Returns the first error encountered while reading the metadata of a runtime directory, such as if it does not
+    /// exist.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_symlinks = dirs.runtime_dirs_symlinks()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_symlinks(&self) -> io::Result<Vec<&Path>> {
+        let mut symlinks = Vec::new();
+        for path in self.as_paths(&self.runtime_dirs) {
+            if fs::symlink_metadata(path)?.file_type().is_symlink() {
+                symlinks.push(path);
+            }
+        }
+
+        Ok(symlinks)
+    }
+
+    /// Returns the path of a socket named `name` in the runtime directory, falling back to the temporary directory.
+    ///
+    /// If there is at least one runtime directory, it returns `name` joined onto the first runtime directory, like
+    /// `/run/myapp/name`. Otherwise, it returns `name` joined onto [`env::temp_dir`], like `/tmp/name`. The socket is
+    /// not created.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let socket_path = dirs.runtime_socket_path("control.sock");
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_socket_path(&self, name: &str) -> PathBuf {
+        match self.runtime_dir() {
+            Some(dir) => dir.join(name),
+            None => env::temp_dir().join(name),
+        }
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file, falling back to the first
+    /// state directory as defined by `StateDirectory`.
+    ///
+    /// Runtime directories take precedence when set, since they are removed when the service stops. Units that need
+    /// the data to survive restarts can configure only a state directory instead. If neither is set, it returns
+    /// [`None`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let dir = dirs.runtime_or_state_dir();
+    /// ```
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    pub fn runtime_or_state_dir(&self) -> Option<&Path> {
+        self.runtime_dir().or_else(|| self.state_dir())
+    }
+
+    /// Waits up to `timeout` for the first runtime directory as defined by `RuntimeDirectory` in the unit file to exist.
+    ///
+    /// Under certain ordering conditions, a directory may not exist for a short time after the service starts. This
+    /// checks whether the directory exists every 10 milliseconds until it does or `timeout` elapses. If there are no
+    /// runtime directories, it returns `Ok(None)` immediately.
+    ///
+    /// # Errors
+    /// Returns an error of kind [`io::ErrorKind::TimedOut`] if the directory does not exist before `timeout` elapses.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.wait_for_runtime_dir(Duration::from_secs(1))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn wait_for_runtime_dir(&self, timeout: Duration) -> io::Result<Option<&Path>> {
+        /// How long to sleep between checks.
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+        let Some(dir) = self.runtime_dir() else {
+            return Ok(None);
+        };
+
+        let start = Instant::now();
+        while !dir.exists() {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out waiting for {} to exist", dir.display()),
+                ));
+            }
+
+            thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+        }
+
+        Ok(Some(dir))
+    }
+
+    /// Returns whether the first runtime directory as defined by `RuntimeDirectory` in the unit file is owned by the
+    /// effective user of the current process.
+    ///
+    /// This is useful in privilege-dropping scenarios to confirm the directory belongs to the current user before
+    /// writing to it. If there are no runtime directories, it returns `Ok(None)`. The owner of the directory is compared
+    /// with the effective user ID from `geteuid(2)`. This method is only available on Unix.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata of the directory cannot be read.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.runtime_dir_owned_by_me()? == Some(false) {
+    ///     eprintln!("refusing to write to a runtime directory owned by another user");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(all(feature = "runtime", unix))]
+    pub fn runtime_dir_owned_by_me(&self) -> io::Result<Option<bool>> {
+        use std::os::unix::fs::MetadataExt;
+        let Some(dir) = self.runtime_dir() else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            fs::metadata(dir)?.uid() == rustix::process::geteuid().as_raw(),
+        ))
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file that currently exists.
+    ///
+    /// This behaves like [`Self::runtime_dir`], but skips paths for which [`Path::exists`] is false.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir_first_existing();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_first_existing(&self) -> Option<&Path> {
+        self.as_paths(&self.runtime_dirs).find(|p| p.exists())
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file that currently exists.
+    ///
+    /// This behaves like [`Self::state_dir`], but skips paths for which [`Path::exists`] is false.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir_first_existing();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_first_existing(&self) -> Option<&Path> {
+        self.as_paths(&self.state_dirs).find(|p| p.exists())
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file that currently exists.
+    ///
+    /// This behaves like [`Self::cache_dir`], but skips paths for which [`Path::exists`] is false.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir_first_existing();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_first_existing(&self) -> Option<&Path> {
+        self.as_paths(&self.cache_dirs).find(|p| p.exists())
+    }
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file that currently exists.
+    ///
+    /// This behaves like [`Self::logs_dir`], but skips paths for which [`Path::exists`] is false.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir_first_existing();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_first_existing(&self) -> Option<&Path> {
+        self.as_paths(&self.logs_dirs).find(|p| p.exists())
+    }
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file that currently exists.
+    ///
+    /// This behaves like [`Self::config_dir`], but skips paths for which [`Path::exists`] is false.
+    /// Unlike the other accessors, this method performs filesystem I/O each time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir_first_existing();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_first_existing(&self) -> Option<&Path> {
+        self.as_paths(&self.config_dirs).find(|p| p.exists())
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file, each paired with whether it
+    /// currently exists.
+    ///
+    /// This behaves like [`Self::runtime_dirs`], but checks each path with [`Path::try_exists`]. It performs a
+    /// filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the existence of any directory cannot be determined, such as when a parent directory is not
+    /// readable.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (dir, exists) in dirs.runtime_dirs_with_existence()? {
+    ///     println!("{}: {exists}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_with_existence(&self) -> io::Result<Vec<(&Path, bool)>> {
+        self.as_paths(&self.runtime_dirs)
+            .map(|p| Ok((p, p.try_exists()?)))
+            .collect()
+    }
+
+    /// Returns all state directories as defined by `StateDirectory` in the unit file, each paired with whether it
+    /// currently exists.
+    ///
+    /// This behaves like [`Self::state_dirs`], but checks each path with [`Path::try_exists`]. It performs a
+    /// filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the existence of any directory cannot be determined, such as when a parent directory is not
+    /// readable.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (dir, exists) in dirs.state_dirs_with_existence()? {
+    ///     println!("{}: {exists}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs_with_existence(&self) -> io::Result<Vec<(&Path, bool)>> {
+        self.as_paths(&self.state_dirs)
+            .map(|p| Ok((p, p.try_exists()?)))
+            .collect()
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file, each paired with whether it
+    /// currently exists.
+    ///
+    /// This behaves like [`Self::cache_dirs`], but checks each path with [`Path::try_exists`]. It performs a
+    /// filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the existence of any directory cannot be determined, such as when a parent directory is not
+    /// readable.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (dir, exists) in dirs.cache_dirs_with_existence()? {
+    ///     println!("{}: {exists}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs_with_existence(&self) -> io::Result<Vec<(&Path, bool)>> {
+        self.as_paths(&self.cache_dirs)
+            .map(|p| Ok((p, p.try_exists()?)))
+            .collect()
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file, each paired with whether it
+    /// currently exists.
+    ///
+    /// This behaves like [`Self::logs_dirs`], but checks each path with [`Path::try_exists`]. It performs a
+    /// filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the existence of any directory cannot be determined, such as when a parent directory is not
+    /// readable.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (dir, exists) in dirs.logs_dirs_with_existence()? {
+    ///     println!("{}: {exists}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs_with_existence(&self) -> io::Result<Vec<(&Path, bool)>> {
+        self.as_paths(&self.logs_dirs)
+            .map(|p| Ok((p, p.try_exists()?)))
+            .collect()
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file, each paired with whether it
+    /// currently exists.
+    ///
+    /// This behaves like [`Self::config_dirs`], but checks each path with [`Path::try_exists`]. It performs a
+    /// filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the existence of any directory cannot be determined, such as when a parent directory is not
+    /// readable.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (dir, exists) in dirs.config_dirs_with_existence()? {
+    ///     println!("{}: {exists}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_with_existence(&self) -> io::Result<Vec<(&Path, bool)>> {
+        self.as_paths(&self.config_dirs)
+            .map(|p| Ok((p, p.try_exists()?)))
+            .collect()
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file that is writable by the current
+    /// process.
+    ///
+    /// Writability is determined by creating and removing a probe file in each directory, so this method performs
+    /// filesystem I/O for every directory it checks. Directories that are read-only or do not exist are skipped.
+    ///
+    /// # Errors
+    /// Returns an error if probing a directory fails for a reason other than it being read-only or missing.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir_first_writable()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_first_writable(&self) -> io::Result<Option<&Path>> {
+        first_writable(self.as_paths(&self.runtime_dirs))
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file that is writable by the current
+    /// process.
+    ///
+    /// Writability is determined by creating and removing a probe file in each directory, so this method performs
+    /// filesystem I/O for every directory it checks. Directories that are read-only or do not exist are skipped.
+    ///
+    /// # Errors
+    /// Returns an error if probing a directory fails for a reason other than it being read-only or missing.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir_first_writable()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_first_writable(&self) -> io::Result<Option<&Path>> {
+        first_writable(self.as_paths(&self.state_dirs))
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file that is writable by the current
+    /// process.
+    ///
+    /// Writability is determined by creating and removing a probe file in each directory, so this method performs
+    /// filesystem I/O for every directory it checks. Directories that are read-only or do not exist are skipped.
+    ///
+    /// # Errors
+    /// Returns an error if probing a directory fails for a reason other than it being read-only or missing.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir_first_writable()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_first_writable(&self) -> io::Result<Option<&Path>> {
+        first_writable(self.as_paths(&self.cache_dirs))
+    }
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file that is writable by the current
+    /// process.
+    ///
+    /// Writability is determined by creating and removing a probe file in each directory, so this method performs
+    /// filesystem I/O for every directory it checks. Directories that are read-only or do not exist are skipped.
+    ///
+    /// # Errors
+    /// Returns an error if probing a directory fails for a reason other than it being read-only or missing.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir_first_writable()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_first_writable(&self) -> io::Result<Option<&Path>> {
+        first_writable(self.as_paths(&self.logs_dirs))
+    }
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file that is writable by the current
+    /// process.
+    ///
+    /// Writability is determined by creating and removing a probe file in each directory, so this method performs
+    /// filesystem I/O for every directory it checks. Directories that are read-only or do not exist are skipped.
+    ///
+    /// # Errors
+    /// Returns an error if probing a directory fails for a reason other than it being read-only or missing.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir_first_writable()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_first_writable(&self) -> io::Result<Option<&Path>> {
+        first_writable(self.as_paths(&self.config_dirs))
+    }
+
+    /// Returns each configuration directory as defined by `ConfigurationDirectory` in the unit file along with whether
+    /// it is read-only for the current process.
+    ///
+    /// systemd intends configuration directories to be read-only for the service, for example when sandboxing with
+    /// `ProtectSystem=strict`, so this surfaces drift between the intended and the actual setup. A directory is
+    /// read-only if creating a probe file in it fails with a read-only file system or permission error, so this method
+    /// performs filesystem I/O for every directory. The directories are returned in order.
+    ///
+    /// # Errors
+    /// Returns an error if probing a directory fails for any other reason, such as the directory not existing.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (dir, read_only) in dirs.config_dirs_readonly()? {
+    ///     if !read_only {
+    ///         eprintln!("warning: {} is writable", dir.display());
+    ///     }
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_readonly(&self) -> io::Result<Vec<(PathBuf, bool)>> {
+        self.config_dirs
+            .iter()
+            .map(|dir| Ok((dir.clone(), !is_writable(dir)?)))
+            .collect()
+    }
+
+    /// Returns the entries of all configuration directories as defined by `ConfigurationDirectory` in the unit file
+    /// whose file names match `pattern`.
+    ///
+    /// `pattern` is a minimal glob where `*` matches any run of characters and `?` matches exactly one character; all
+    /// other characters match themselves. Only the direct entries of each directory are considered, and entries whose
+    /// names are not valid UTF-8 never match. Matches are grouped by directory in order and sorted within each
+    /// directory. If there are no configuration directories, it returns an empty vector.
+    ///
+    /// # Errors
+    /// Returns an error if any configuration directory cannot be read.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for plugin in dirs.config_dir_glob("*.conf")? {
+    ///     println!("loading {}", plugin.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let mut matches = Vec::new();
+        for dir in &self.config_dirs {
+            let mut entries = Vec::new();
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| glob_match(pattern, name))
+                {
+                    entries.push(entry.path());
+                }
+            }
+
+            entries.sort();
+            matches.append(&mut entries);
+        }
+
+        Ok(matches)
+    }
+
+    /// Returns the path of the first file named `name` across the configuration directories as defined by
+    /// `ConfigurationDirectory` in the unit file.
+    ///
+    /// The directories are searched in order, like a `PATH` search, so earlier directories take precedence. Only
+    /// regular files, or symbolic links to them, match. Returns [`None`] if there are no configuration directories or
+    /// none of them contains the file. This performs a filesystem check for each directory it searches.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if let Some(config) = dirs.find_config_file("app.toml") {
+    ///     println!("loading {}", config.display());
+    /// }
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn find_config_file(&self, name: impl AsRef<Path>) -> Option<PathBuf> {
+        let name = name.as_ref();
+        self.config_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// Returns the configuration directories as defined by `ConfigurationDirectory` in the unit file as a
+    /// [`SearchPath`].
+    ///
+    /// This packages the search of [`Self::find_config_file`] behind a type that can be passed to APIs accepting an
+    /// ordered search path. If there are no configuration directories, the search path is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let search_path = dirs.config_search_path();
+    /// if let Some(config) = search_path.find("app.toml") {
+    ///     println!("loading {}", config.display());
+    /// }
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_search_path(&self) -> SearchPath {
+        SearchPath::from(self.config_dirs.clone())
+    }
+
+    /// Creates a new, uniquely named temporary file in the first state directory as defined by `StateDirectory` in the
+    /// unit file, returning the open file and its path.
+    ///
+    /// Since the file is in the same directory as the state it replaces, it can later be renamed over that state with
+    /// [`fs::rename`], which is atomic on the same filesystem. The file is created exclusively, so it never overwrites
+    /// an existing file, and it is not removed automatically. If there are no state directories, it returns
+    /// `Ok(None)`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be created, such as when the directory does not exist or is read-only.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::fs;
+    /// use std::io::Write;
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if let Some((mut file, path)) = dirs.state_tempfile()? {
+    ///     file.write_all(b"new state")?;
+    ///     file.sync_all()?;
+    ///     fs::rename(path, dirs.state_dir().unwrap().join("state"))?;
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_tempfile(&self) -> io::Result<Option<(fs::File, PathBuf)>> {
+        self.state_dir()
+            .map(|dir| create_unique(dir, ".tmp-"))
+            .transpose()
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file, sorted and deduplicated.
+    ///
+    /// This behaves like [`Self::runtime_dirs`], but the paths are sorted lexically by component and duplicates are
+    /// removed. The order stored in the snapshot is not modified.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dirs = dirs.runtime_dirs_sorted();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_sorted(&self) -> Vec<&Path> {
+        let mut dirs = self.runtime_dirs();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns all state directories as defined by `StateDirectory` in the unit file, sorted and deduplicated.
+    ///
+    /// This behaves like [`Self::state_dirs`], but the paths are sorted lexically by component and duplicates are
+    /// removed. The order stored in the snapshot is not modified.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dirs = dirs.state_dirs_sorted();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs_sorted(&self) -> Vec<&Path> {
+        let mut dirs = self.state_dirs();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file, sorted and deduplicated.
+    ///
+    /// This behaves like [`Self::cache_dirs`], but the paths are sorted lexically by component and duplicates are
+    /// removed. The order stored in the snapshot is not modified.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dirs = dirs.cache_dirs_sorted();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs_sorted(&self) -> Vec<&Path> {
+        let mut dirs = self.cache_dirs();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file, sorted and deduplicated.
+    ///
+    /// This behaves like [`Self::logs_dirs`], but the paths are sorted lexically by component and duplicates are
+    /// removed. The order stored in the snapshot is not modified.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dirs = dirs.logs_dirs_sorted();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs_sorted(&self) -> Vec<&Path> {
+        let mut dirs = self.logs_dirs();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file, sorted and deduplicated.
+    ///
+    /// This behaves like [`Self::config_dirs`], but the paths are sorted lexically by component and duplicates are
+    /// removed. The order stored in the snapshot is not modified.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dirs = dirs.config_dirs_sorted();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_sorted(&self) -> Vec<&Path> {
+        let mut dirs = self.config_dirs();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns `true` if more than one runtime directory is defined by `RuntimeDirectory` in the unit file.
+    ///
+    /// This is useful to warn when code only handles the first directory from [`Self::runtime_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.runtime_is_multiple() {
+    ///     eprintln!("warning: only the first runtime directory is used");
+    /// }
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_is_multiple(&self) -> bool {
+        self.runtime_dirs.len() > 1
+    }
+
+    /// Returns `true` if more than one state directory is defined by `StateDirectory` in the unit file.
+    ///
+    /// This is useful to warn when code only handles the first directory from [`Self::state_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.state_is_multiple() {
+    ///     eprintln!("warning: only the first state directory is used");
+    /// }
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_is_multiple(&self) -> bool {
+        self.state_dirs.len() > 1
+    }
+
+    /// Returns `true` if more than one cache directory is defined by `CacheDirectory` in the unit file.
+    ///
+    /// This is useful to warn when code only handles the first directory from [`Self::cache_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.cache_is_multiple() {
+    ///     eprintln!("warning: only the first cache directory is used");
+    /// }
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_is_multiple(&self) -> bool {
+        self.cache_dirs.len() > 1
+    }
+
+    /// Returns `true` if more than one logs directory is defined by `LogsDirectory` in the unit file.
+    ///
+    /// This is useful to warn when code only handles the first directory from [`Self::logs_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.logs_is_multiple() {
+    ///     eprintln!("warning: only the first logs directory is used");
+    /// }
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_is_multiple(&self) -> bool {
+        self.logs_dirs.len() > 1
+    }
+
+    /// Returns `true` if more than one configuration directory is defined by `ConfigurationDirectory` in the unit file.
+    ///
+    /// This is useful to warn when code only handles the first directory from [`Self::config_dir`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.config_is_multiple() {
+    ///     eprintln!("warning: only the first configuration directory is used");
+    /// }
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_is_multiple(&self) -> bool {
+        self.config_dirs.len() > 1
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file for which `f` returns `true`.
+    ///
+    /// This behaves like [`Self::runtime_dirs`], but only returns the paths matching the predicate.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dirs = dirs.runtime_dirs_filter(|p| p.starts_with("/run"));
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_filter<F: Fn(&Path) -> bool>(&self, f: F) -> Vec<&Path> {
+        self.as_paths(&self.runtime_dirs).filter(|p| f(p)).collect()
+    }
+
+    /// Returns all state directories as defined by `StateDirectory` in the unit file for which `f` returns `true`.
+    ///
+    /// This behaves like [`Self::state_dirs`], but only returns the paths matching the predicate.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dirs = dirs.state_dirs_filter(|p| p.starts_with("/var/lib"));
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs_filter<F: Fn(&Path) -> bool>(&self, f: F) -> Vec<&Path> {
+        self.as_paths(&self.state_dirs).filter(|p| f(p)).collect()
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file for which `f` returns `true`.
+    ///
+    /// This behaves like [`Self::cache_dirs`], but only returns the paths matching the predicate.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dirs = dirs.cache_dirs_filter(|p| p.starts_with("/var/cache"));
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs_filter<F: Fn(&Path) -> bool>(&self, f: F) -> Vec<&Path> {
+        self.as_paths(&self.cache_dirs).filter(|p| f(p)).collect()
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file for which `f` returns `true`.
+    ///
+    /// This behaves like [`Self::logs_dirs`], but only returns the paths matching the predicate.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dirs = dirs.logs_dirs_filter(|p| p.starts_with("/var/log"));
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs_filter<F: Fn(&Path) -> bool>(&self, f: F) -> Vec<&Path> {
+        self.as_paths(&self.logs_dirs).filter(|p| f(p)).collect()
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file for which `f` returns `true`.
+    ///
+    /// This behaves like [`Self::config_dirs`], but only returns the paths matching the predicate.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dirs = dirs.config_dirs_filter(|p| p.starts_with("/etc"));
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_filter<F: Fn(&Path) -> bool>(&self, f: F) -> Vec<&Path> {
+        self.as_paths(&self.config_dirs).filter(|p| f(p)).collect()
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file with `suffix` joined onto each.
+    ///
+    /// This behaves like [`Self::runtime_dirs`], but joins `suffix` with [`Path::join`], so an absolute `suffix` replaces
+    /// the directory entirely.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dirs = dirs.runtime_dirs_with_suffix("myapp/data");
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_with_suffix(&self, suffix: impl AsRef<Path>) -> Vec<PathBuf> {
+        let suffix = suffix.as_ref();
+        self.as_paths(&self.runtime_dirs)
+            .map(|p| p.join(suffix))
+            .collect()
+    }
+
+    /// Returns all state directories as defined by `StateDirectory` in the unit file with `suffix` joined onto each.
+    ///
+    /// This behaves like [`Self::state_dirs`], but joins `suffix` with [`Path::join`], so an absolute `suffix` replaces
+    /// the directory entirely.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dirs = dirs.state_dirs_with_suffix("myapp/data");
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs_with_suffix(&self, suffix: impl AsRef<Path>) -> Vec<PathBuf> {
+        let suffix = suffix.as_ref();
+        self.as_paths(&self.state_dirs)
+            .map(|p| p.join(suffix))
+            .collect()
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file with `suffix` joined onto each.
+    ///
+    /// This behaves like [`Self::cache_dirs`], but joins `suffix` with [`Path::join`], so an absolute `suffix` replaces
+    /// the directory entirely.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dirs = dirs.cache_dirs_with_suffix("myapp/data");
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs_with_suffix(&self, suffix: impl AsRef<Path>) -> Vec<PathBuf> {
+        let suffix = suffix.as_ref();
+        self.as_paths(&self.cache_dirs)
+            .map(|p| p.join(suffix))
+            .collect()
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file with `suffix` joined onto each.
+    ///
+    /// This behaves like [`Self::logs_dirs`], but joins `suffix` with [`Path::join`], so an absolute `suffix` replaces
+    /// the directory entirely.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dirs = dirs.logs_dirs_with_suffix("myapp/data");
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs_with_suffix(&self, suffix: impl AsRef<Path>) -> Vec<PathBuf> {
+        let suffix = suffix.as_ref();
+        self.as_paths(&self.logs_dirs)
+            .map(|p| p.join(suffix))
+            .collect()
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file with `suffix` joined onto each.
+    ///
+    /// This behaves like [`Self::config_dirs`], but joins `suffix` with [`Path::join`], so an absolute `suffix` replaces
+    /// the directory entirely.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dirs = dirs.config_dirs_with_suffix("myapp/data");
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_with_suffix(&self, suffix: impl AsRef<Path>) -> Vec<PathBuf> {
+        let suffix = suffix.as_ref();
+        self.as_paths(&self.config_dirs)
+            .map(|p| p.join(suffix))
+            .collect()
+    }
+
+    /// Returns the runtime directories as defined by `RuntimeDirectory` in the unit file, each relative to the current
+    /// working directory if it is under it.
+    ///
+    /// This is useful for compact status output. Directories that are not under the current working directory are
+    /// returned unchanged, and the current working directory itself is returned as `.`. Paths are compared lexically,
+    /// without resolving symbolic links.
+    ///
+    /// # Errors
+    /// Returns an error if the current working directory cannot be determined.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for dir in dirs.runtime_dirs_relative_to_cwd()? {
+    ///     println!("{}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_relative_to_cwd(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(relative_to(&self.runtime_dirs, &env::current_dir()?))
+    }
+
+    /// Returns the state directories as defined by `StateDirectory` in the unit file, each relative to the current
+    /// working directory if it is under it.
+    ///
+    /// This is useful for compact status output. Directories that are not under the current working directory are
+    /// returned unchanged, and the current working directory itself is returned as `.`. Paths are compared lexically,
+    /// without resolving symbolic links.
+    ///
+    /// # Errors
+    /// Returns an error if the current working directory cannot be determined.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for dir in dirs.state_dirs_relative_to_cwd()? {
+    ///     println!("{}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs_relative_to_cwd(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(relative_to(&self.state_dirs, &env::current_dir()?))
+    }
+
+    /// Returns the cache directories as defined by `CacheDirectory` in the unit file, each relative to the current
+    /// working directory if it is under it.
+    ///
+    /// This is useful for compact status output. Directories that are not under the current working directory are
+    /// returned unchanged, and the current working directory itself is returned as `.`. Paths are compared lexically,
+    /// without resolving symbolic links.
+    ///
+    /// # Errors
+    /// Returns an error if the current working directory cannot be determined.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for dir in dirs.cache_dirs_relative_to_cwd()? {
+    ///     println!("{}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs_relative_to_cwd(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(relative_to(&self.cache_dirs, &env::current_dir()?))
+    }
+
+    /// Returns the logs directories as defined by `LogsDirectory` in the unit file, each relative to the current
+    /// working directory if it is under it.
+    ///
+    /// This is useful for compact status output. Directories that are not under the current working directory are
+    /// returned unchanged, and the current working directory itself is returned as `.`. Paths are compared lexically,
+    /// without resolving symbolic links.
+    ///
+    /// # Errors
+    /// Returns an error if the current working directory cannot be determined.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for dir in dirs.logs_dirs_relative_to_cwd()? {
+    ///     println!("{}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs_relative_to_cwd(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(relative_to(&self.logs_dirs, &env::current_dir()?))
+    }
+
+    /// Returns the configuration directories as defined by `ConfigurationDirectory` in the unit file, each relative to the current
+    /// working directory if it is under it.
+    ///
+    /// This is useful for compact status output. Directories that are not under the current working directory are
+    /// returned unchanged, and the current working directory itself is returned as `.`. Paths are compared lexically,
+    /// without resolving symbolic links.
+    ///
+    /// # Errors
+    /// Returns an error if the current working directory cannot be determined.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for dir in dirs.config_dirs_relative_to_cwd()? {
+    ///     println!("{}", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs_relative_to_cwd(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(relative_to(&self.config_dirs, &env::current_dir()?))
+    }
+
+    /// Returns the first runtime directory as a [`SystemdDir`] of kind [`DirKind::Runtime`].
+    ///
+    /// This behaves like [`Self::runtime_dir`], but the returned path remembers which variable it came from.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir_typed();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_typed(&self) -> Option<SystemdDir> {
+        self.runtime_dir()
+            .map(|p| SystemdDir::new(DirKind::Runtime, p.to_path_buf()))
+    }
+
+    /// Returns the first state directory as a [`SystemdDir`] of kind [`DirKind::State`].
+    ///
+    /// This behaves like [`Self::state_dir`], but the returned path remembers which variable it came from.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir_typed();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_typed(&self) -> Option<SystemdDir> {
+        self.state_dir()
+            .map(|p| SystemdDir::new(DirKind::State, p.to_path_buf()))
+    }
+
+    /// Returns the first cache directory as a [`SystemdDir`] of kind [`DirKind::Cache`].
+    ///
+    /// This behaves like [`Self::cache_dir`], but the returned path remembers which variable it came from.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir_typed();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_typed(&self) -> Option<SystemdDir> {
+        self.cache_dir()
+            .map(|p| SystemdDir::new(DirKind::Cache, p.to_path_buf()))
+    }
+
+    /// Returns the first logs directory as a [`SystemdDir`] of kind [`DirKind::Logs`].
+    ///
+    /// This behaves like [`Self::logs_dir`], but the returned path remembers which variable it came from.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir_typed();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_typed(&self) -> Option<SystemdDir> {
+        self.logs_dir()
+            .map(|p| SystemdDir::new(DirKind::Logs, p.to_path_buf()))
+    }
+
+    /// Returns the first configuration directory as a [`SystemdDir`] of kind [`DirKind::Config`].
+    ///
+    /// This behaves like [`Self::config_dir`], but the returned path remembers which variable it came from.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir_typed();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_typed(&self) -> Option<SystemdDir> {
+        self.config_dir()
+            .map(|p| SystemdDir::new(DirKind::Config, p.to_path_buf()))
+    }
+
+    /// Returns the first runtime directory as a [`RuntimeDirectory`], marking its kind at the type level.
+    ///
+    /// This behaves like [`Self::runtime_dir`], but the returned path can only be passed where a runtime directory is
+    /// expected. Unlike [`Self::runtime_dir_typed`], the kind is checked at compile time and nothing is cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir_marked();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_marked(&self) -> Option<RuntimeDirectory<'_>> {
+        self.runtime_dir().map(RuntimeDirectory)
+    }
+
+    /// Returns the first state directory as a [`StateDirectory`], marking its kind at the type level.
+    ///
+    /// This behaves like [`Self::state_dir`], but the returned path can only be passed where a state directory is
+    /// expected. Unlike [`Self::state_dir_typed`], the kind is checked at compile time and nothing is cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir_marked();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_marked(&self) -> Option<StateDirectory<'_>> {
+        self.state_dir().map(StateDirectory)
+    }
+
+    /// Returns the first cache directory as a [`CacheDirectory`], marking its kind at the type level.
+    ///
+    /// This behaves like [`Self::cache_dir`], but the returned path can only be passed where a cache directory is
+    /// expected. Unlike [`Self::cache_dir_typed`], the kind is checked at compile time and nothing is cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir_marked();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_marked(&self) -> Option<CacheDirectory<'_>> {
+        self.cache_dir().map(CacheDirectory)
+    }
+
+    /// Returns the first logs directory as a [`LogsDirectory`], marking its kind at the type level.
+    ///
+    /// This behaves like [`Self::logs_dir`], but the returned path can only be passed where a logs directory is
+    /// expected. Unlike [`Self::logs_dir_typed`], the kind is checked at compile time and nothing is cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir_marked();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_marked(&self) -> Option<LogsDirectory<'_>> {
+        self.logs_dir().map(LogsDirectory)
+    }
+
+    /// Returns the first configuration directory as a [`ConfigurationDirectory`], marking its kind at the type level.
+    ///
+    /// This behaves like [`Self::config_dir`], but the returned path can only be passed where a configuration directory is
+    /// expected. Unlike [`Self::config_dir_typed`], the kind is checked at compile time and nothing is cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir_marked();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_marked(&self) -> Option<ConfigurationDirectory<'_>> {
+        self.config_dir().map(ConfigurationDirectory)
+    }
+
+    /// Returns the runtime directory as defined by `RuntimeDirectory` in the unit file with the fewest path components.
+    ///
+    /// This is useful for picking the shallowest directory as a default root when several are configured. If several
+    /// directories have the fewest components, the first of them is returned. If there are no runtime directories, it
+    /// returns [`None`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir_shortest();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_shortest(&self) -> Option<&Path> {
+        self.as_paths(&self.runtime_dirs)
+            .min_by_key(|dir| dir.components().count())
+    }
+
+    /// Returns the state directory as defined by `StateDirectory` in the unit file with the fewest path components.
+    ///
+    /// This is useful for picking the shallowest directory as a default root when several are configured. If several
+    /// directories have the fewest components, the first of them is returned. If there are no state directories, it
+    /// returns [`None`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir_shortest();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_shortest(&self) -> Option<&Path> {
+        self.as_paths(&self.state_dirs)
+            .min_by_key(|dir| dir.components().count())
+    }
+
+    /// Returns the cache directory as defined by `CacheDirectory` in the unit file with the fewest path components.
+    ///
+    /// This is useful for picking the shallowest directory as a default root when several are configured. If several
+    /// directories have the fewest components, the first of them is returned. If there are no cache directories, it
+    /// returns [`None`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir_shortest();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_shortest(&self) -> Option<&Path> {
+        self.as_paths(&self.cache_dirs)
+            .min_by_key(|dir| dir.components().count())
+    }
+
+    /// Returns the logs directory as defined by `LogsDirectory` in the unit file with the fewest path components.
+    ///
+    /// This is useful for picking the shallowest directory as a default root when several are configured. If several
+    /// directories have the fewest components, the first of them is returned. If there are no logs directories, it
+    /// returns [`None`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir_shortest();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_shortest(&self) -> Option<&Path> {
+        self.as_paths(&self.logs_dirs)
+            .min_by_key(|dir| dir.components().count())
+    }
+
+    /// Returns the configuration directory as defined by `ConfigurationDirectory` in the unit file with the fewest path components.
+    ///
+    /// This is useful for picking the shallowest directory as a default root when several are configured. If several
+    /// directories have the fewest components, the first of them is returned. If there are no configuration directories, it
+    /// returns [`None`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir_shortest();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_shortest(&self) -> Option<&Path> {
+        self.as_paths(&self.config_dirs)
+            .min_by_key(|dir| dir.components().count())
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file as an owned [`PathBuf`].
+    ///
+    /// This behaves like [`Self::runtime_dir`], but clones the path so the return type matches the standalone
+    /// [`runtime_dir`] function.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir = dirs.runtime_dir_buf();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_buf(&self) -> Option<PathBuf> {
+        self.runtime_dir().map(Path::to_path_buf)
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file as an owned [`PathBuf`].
+    ///
+    /// This behaves like [`Self::state_dir`], but clones the path so the return type matches the standalone
+    /// [`state_dir`] function.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir = dirs.state_dir_buf();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_buf(&self) -> Option<PathBuf> {
+        self.state_dir().map(Path::to_path_buf)
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file as an owned [`PathBuf`].
+    ///
+    /// This behaves like [`Self::cache_dir`], but clones the path so the return type matches the standalone
+    /// [`cache_dir`] function.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir = dirs.cache_dir_buf();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_buf(&self) -> Option<PathBuf> {
+        self.cache_dir().map(Path::to_path_buf)
+    }
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file as an owned [`PathBuf`].
+    ///
+    /// This behaves like [`Self::logs_dir`], but clones the path so the return type matches the standalone
+    /// [`logs_dir`] function.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir = dirs.logs_dir_buf();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_buf(&self) -> Option<PathBuf> {
+        self.logs_dir().map(Path::to_path_buf)
+    }
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file as an owned [`PathBuf`].
+    ///
+    /// This behaves like [`Self::config_dir`], but clones the path so the return type matches the standalone
+    /// [`config_dir`] function.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir = dirs.config_dir_buf();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_buf(&self) -> Option<PathBuf> {
+        self.config_dir().map(Path::to_path_buf)
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file as a string slice.
+    ///
+    /// This behaves like [`Self::runtime_dir`], but returns [`None`] both when there are no runtime directories and when
+    /// the first one is not valid UTF-8. Use [`Self::runtime_dir`] to tell the two cases apart.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let runtime_dir: Option<&str> = dirs.runtime_dir_str();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir_str(&self) -> Option<&str> {
+        self.runtime_dir().and_then(Path::to_str)
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file as a string slice.
+    ///
+    /// This behaves like [`Self::state_dir`], but returns [`None`] both when there are no state directories and when
+    /// the first one is not valid UTF-8. Use [`Self::state_dir`] to tell the two cases apart.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let state_dir: Option<&str> = dirs.state_dir_str();
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir_str(&self) -> Option<&str> {
+        self.state_dir().and_then(Path::to_str)
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file as a string slice.
+    ///
+    /// This behaves like [`Self::cache_dir`], but returns [`None`] both when there are no cache directories and when
+    /// the first one is not valid UTF-8. Use [`Self::cache_dir`] to tell the two cases apart.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let cache_dir: Option<&str> = dirs.cache_dir_str();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir_str(&self) -> Option<&str> {
+        self.cache_dir().and_then(Path::to_str)
+    }
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file as a string slice.
+    ///
+    /// This behaves like [`Self::logs_dir`], but returns [`None`] both when there are no logs directories and when
+    /// the first one is not valid UTF-8. Use [`Self::logs_dir`] to tell the two cases apart.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let logs_dir: Option<&str> = dirs.logs_dir_str();
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir_str(&self) -> Option<&str> {
+        self.logs_dir().and_then(Path::to_str)
+    }
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file as a string slice.
+    ///
+    /// This behaves like [`Self::config_dir`], but returns [`None`] both when there are no configuration directories and when
+    /// the first one is not valid UTF-8. Use [`Self::config_dir`] to tell the two cases apart.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let config_dir: Option<&str> = dirs.config_dir_str();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir_str(&self) -> Option<&str> {
+        self.config_dir().and_then(Path::to_str)
+    }
+
+    /// Returns the longest common path prefix of all runtime directories.
+    ///
+    /// The prefix is computed by comparing the components of each runtime directory, so `/run/a/b` and `/run/a/c`
+    /// have the common root `/run/a` while `/run/ab` and `/run/ac` have the common root `/run`. If there is only one
+    /// runtime directory, it is its own common root.
+    ///
+    /// Returns [`None`] if there are no runtime directories or if they share nothing but the filesystem root, so the
+    /// result is never `/` itself.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if let Some(root) = dirs.runtime_dirs_common_root() {
+    ///     // --snip--
+    /// }
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs_common_root(&self) -> Option<PathBuf> {
+        let mut paths = self.as_paths(&self.runtime_dirs);
+        let mut common: Vec<Component> = paths.next()?.components().collect();
+        for path in paths {
+            let shared = common
+                .iter()
+                .zip(path.components())
+                .take_while(|(a, b)| *a == b)
+                .count();
+
+            common.truncate(shared);
+        }
+
+        if common.iter().any(|c| matches!(c, Component::Normal(_))) {
+            Some(common.iter().collect())
+        } else {
+            None
+        }
+    }
+
+    /// Helper to replace every directory of every kind with the result of `f`.
+    fn map_dirs(mut self, f: impl Fn(&Path) -> PathBuf) -> Self {
+        for kind in DirKind::ALL {
+            for dir in self.get_mut(kind).into_iter().flatten() {
+                *dir = f(dir);
+            }
+        }
+
+        self
+    }
+
+    /// Returns the kinds whose directories differ between this snapshot and `other`.
+    ///
+    /// Two snapshots differ in a kind if they have different paths or the same paths in a different order. Combined
+    /// with periodically taking new snapshots, this can detect configuration changes, such as after a unit file was
+    /// changed and the service restarted. The kinds are returned in the order of [`DirKind::ALL`].
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let before = SystemdDirs::new();
+    /// // --snip--
+    /// let after = SystemdDirs::new();
+    /// for kind in after.changed_since(&before) {
+    ///     println!("{} changed", kind.env_key());
+    /// }
+    /// ```
+    pub fn changed_since(&self, other: &SystemdDirs) -> Vec<DirKind> {
+        DirKind::ALL
+            .into_iter()
+            .filter(|&kind| self.get(kind) != other.get(kind))
+            .collect()
+    }
+
+    /// Returns whether this snapshot and `other` have the same directories of each kind, ignoring their order and
+    /// duplicates.
+    ///
+    /// Unlike `==`, which requires the directories of each kind to be in the same order, this compares them as sets.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::PathBuf;
+    /// use systemd_directories::SystemdDirs;
+    /// let a = SystemdDirs::new().with_state_dirs(vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+    /// let b = SystemdDirs::new().with_state_dirs(vec![PathBuf::from("/b"), PathBuf::from("/a")]);
+    /// assert!(a.eq_ignoring_order(&b));
+    /// ```
+    pub fn eq_ignoring_order(&self, other: &SystemdDirs) -> bool {
+        DirKind::ALL.into_iter().all(|kind| {
+            let ours: HashSet<_> = self.get(kind).iter().collect();
+            let theirs: HashSet<_> = other.get(kind).iter().collect();
+            ours == theirs
+        })
+    }
+
+    /// Returns a human-readable summary of the directories added and removed since `previous`, one line per kind.
+    ///
+    /// Each line has the form `cache: added /var/cache/new, removed /var/cache/old`, and only kinds whose set of
+    /// directories changed appear, in the order of [`DirKind::ALL`]. Like [`Self::eq_ignoring_order`], reordering the
+    /// directories of a kind is not a change. This is meant for logging after re-reading the configuration, such as on
+    /// reload.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let previous = SystemdDirs::new();
+    /// // --snip--
+    /// let current = SystemdDirs::new();
+    /// for line in current.diff_report(&previous) {
+    ///     println!("{line}");
+    /// }
+    /// ```
+    pub fn diff_report(&self, previous: &SystemdDirs) -> Vec<String> {
+        DirKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let (current, previous) = (self.get(kind), previous.get(kind));
+                let added = current.iter().filter(|dir| !previous.contains(dir));
+                let removed = previous.iter().filter(|dir| !current.contains(dir));
+                let changes: Vec<String> = added
+                    .map(|dir| format!("added {}", dir.display()))
+                    .chain(removed.map(|dir| format!("removed {}", dir.display())))
+                    .collect();
+                (!changes.is_empty()).then(|| format!("{kind}: {}", changes.join(", ")))
+            })
+            .collect()
+    }
+
+    /// Returns this snapshot with the directories of each kind replaced by those of `overlay`, for every kind where
+    /// `overlay` has at least one directory.
+    ///
+    /// Directories are replaced per kind, not concatenated: a kind takes either all of the directories of `overlay` or
+    /// all of the directories of this snapshot. This supports layered configuration, such as a snapshot read from a
+    /// file with [`Self::from_env_file`] overriding the one from the environment. Whether a kind's variable was set,
+    /// as returned by [`Self::present_vars`], follows the snapshot its directories came from.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use systemd_directories::SystemdDirs;
+    /// let base = SystemdDirs::new();
+    /// let dirs = base.merge(SystemdDirs::from_env_file("overrides.env")?);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn merge(mut self, mut overlay: SystemdDirs) -> SystemdDirs {
+        for kind in DirKind::ALL {
+            let (Some(dirs), Some(overlay_dirs)) = (self.get_mut(kind), overlay.get_mut(kind))
+            else {
+                continue;
+            };
+
+            if overlay_dirs.is_empty() {
+                continue;
+            }
+
+            *dirs = mem::take(overlay_dirs);
+            self.present.retain(|&(present, _, _)| present != kind);
+            self.present.extend(
+                overlay
+                    .present
+                    .iter()
+                    .filter(|&&(present, _, _)| present == kind),
+            );
+        }
+
+        self.present.sort_by_key(|&(kind, _, _)| kind);
+        self
+    }
+
+    /// Returns an iterator over every directory of every kind, tagged with its kind and zero-based index within it.
+    ///
+    /// The kinds are walked in the order of [`DirKind::ALL`], and the directories of each kind in the order they
+    /// appear in the environment variable.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (kind, index, path) in dirs.flat_iter() {
+    ///     println!("{}[{index}] = {}", kind.env_key(), path.display());
+    /// }
+    /// ```
+    pub fn flat_iter(&self) -> impl Iterator<Item = (DirKind, usize, &Path)> {
+        DirKind::ALL.into_iter().flat_map(move |kind| {
+            self.as_paths(self.get(kind))
+                .enumerate()
+                .map(move |(index, path)| (kind, index, path))
+        })
+    }
+
+    /// Returns every directory of every kind tagged with its kind, sorted by path and then by kind.
+    ///
+    /// Unlike [`Self::flat_iter`], the order does not depend on the order of the kinds or of the paths within each
+    /// environment variable, which makes it suitable for deterministic output such as audit logs.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (kind, path) in dirs.sorted_entries() {
+    ///     println!("{kind} {}", path.display());
+    /// }
+    /// ```
+    pub fn sorted_entries(&self) -> Vec<(DirKind, &Path)> {
+        let mut entries: Vec<_> = self.flat_iter().map(|(kind, _, dir)| (kind, dir)).collect();
+        entries.sort_by_key(|&(kind, dir)| (dir, kind));
+        entries
+    }
+
+    /// Returns every directory of every kind along with whether it currently exists on disk.
+    ///
+    /// The directories are listed in the same order as [`Self::flat_iter`]. This performs a filesystem check for each
+    /// directory every time it is called, which makes it suitable for health checks that verify the unit created
+    /// everything it should have.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (kind, path, exists) in dirs.existence_report() {
+    ///     if !exists {
+    ///         eprintln!("{kind} directory {} is missing", path.display());
+    ///     }
+    /// }
+    /// ```
+    pub fn existence_report(&self) -> Vec<(DirKind, PathBuf, bool)> {
+        self.flat_iter()
+            .map(|(kind, _, dir)| (kind, dir.to_path_buf(), dir.exists()))
+            .collect()
+    }
+
+    /// Returns the summed size in bytes of the regular files under every directory of `kind`, recursively.
+    ///
+    /// Symbolic links inside the directories are skipped rather than followed, so files outside them are never counted
+    /// and links cannot cause cycles. Only the apparent length of each file is summed, not the blocks it occupies on
+    /// disk. If there are no directories of `kind`, it returns `0`. This walks the whole tree every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if any directory or entry under it cannot be read, such as when a directory does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// println!("cache is using {} bytes", dirs.disk_usage(DirKind::Cache)?);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn disk_usage(&self, kind: DirKind) -> io::Result<u64> {
+        self.get(kind).iter().map(|dir| disk_usage(dir)).sum()
+    }
+
+    /// Returns the paths of the regular files under every directory of `kind`, recursively.
+    ///
+    /// Like [`Self::disk_usage`], symbolic links inside the directories are skipped rather than followed, so files
+    /// outside them are never listed and links cannot cause cycles. The files are grouped by directory in order and
+    /// sorted by path within each directory, and each path is joined onto its configured directory, so it is absolute
+    /// whenever the directory is. If there are no directories of `kind`, it returns an empty vector. This walks the
+    /// whole tree every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if any directory or entry under it cannot be read, such as when a directory does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// for file in dirs.walk_files(DirKind::State)? {
+    ///     println!("backing up {}", file.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn walk_files(&self, kind: DirKind) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for dir in self.get(kind) {
+            walk_files(dir, &mut files)?;
+        }
+
+        Ok(files)
+    }
+
+    /// Returns the directories of `kind` whose modification time is later than `since`.
+    ///
+    /// A directory's modification time changes when entries are added, removed, or renamed directly inside it, but not
+    /// when files inside it are modified in place or when its subdirectories change. It performs a filesystem check for
+    /// each directory every time it is called. If there are no directories of `kind`, it returns an empty vector.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata of any directory cannot be read, such as when it does not exist, or if the
+    /// platform does not support modification times.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+    /// for dir in dirs.modified_since(DirKind::State, an_hour_ago)? {
+    ///     println!("{} changed in the last hour", dir.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn modified_since(&self, kind: DirKind, since: SystemTime) -> io::Result<Vec<&Path>> {
+        let mut modified = Vec::new();
+        for dir in self.as_paths(self.get(kind)) {
+            if fs::metadata(dir)?.modified()? > since {
+                modified.push(dir);
+            }
+        }
+
+        Ok(modified)
+    }
+
+    /// Returns the space in bytes available to unprivileged users on the filesystem backing the first directory of
+    /// `kind`, or `Ok(None)` if there are no directories of `kind`.
+    ///
+    /// The space is read with `statvfs(3)` as the number of blocks available to unprivileged users times the
+    /// fragment size, so it excludes any space reserved for root.
+    ///
+    /// This function is only available on Unix with the `statvfs` feature.
+    ///
+    /// # Errors
+    /// Returns an error if `statvfs` fails, such as when the directory does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// if let Some(available) = dirs.available_space(DirKind::Cache)? {
+    ///     println!("{available} bytes available for the cache");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(all(unix, feature = "statvfs"))]
+    pub fn available_space(&self, kind: DirKind) -> io::Result<Option<u64>> {
+        let Some(dir) = self.get(kind).first() else {
+            return Ok(None);
+        };
+
+        let stat = rustix::fs::statvfs(dir)?;
+        Ok(Some(stat.f_bavail.saturating_mul(stat.f_frsize)))
+    }
+
+    /// Returns every directory of every kind grouped by the device of the filesystem it lives on.
+    ///
+    /// The keys are the `st_dev` of each directory, and the directories of each group are in the same order as
+    /// [`Self::flat_iter`]. This reveals when, for example, the runtime and cache directories share a filesystem. It
+    /// performs a filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata of any directory cannot be read, such as when it does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (device, paths) in dirs.group_by_device()? {
+    ///     println!("device {device}: {paths:?}");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn group_by_device(&self) -> io::Result<HashMap<u64, Vec<&Path>>> {
+        use std::os::unix::fs::MetadataExt;
+        let mut groups: HashMap<u64, Vec<&Path>> = HashMap::new();
+        for (_, _, dir) in self.flat_iter() {
+            groups
+                .entry(fs::metadata(dir)?.dev())
+                .or_default()
+                .push(dir);
+        }
+
+        Ok(groups)
+    }
+
+    /// Returns the number of distinct devices that the directories of all kinds live on.
+    ///
+    /// This is the number of groups in [`Self::group_by_device`], so it is zero if there are no directories. It
+    /// performs a filesystem check for each directory every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata of any directory cannot be read, such as when it does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.distinct_device_count()? > 1 {
+    ///     println!("directories span multiple filesystems");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn distinct_device_count(&self) -> io::Result<usize> {
+        self.group_by_device().map(|groups| groups.len())
+    }
+
+    /// Returns whether the first runtime and first state directories live on the same filesystem, or [`None`] if
+    /// either is unset.
+    ///
+    /// Runtime directories are meant to be ephemeral, usually on a tmpfs under `/run`, while state directories are
+    /// meant to persist, so sharing a filesystem can indicate a misconfiguration. The filesystems are compared by the
+    /// `st_dev` of each directory, so bind mounts of the same filesystem count as co-located. It performs a filesystem
+    /// check every time it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata of either directory cannot be read, such as when it does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.runtime_state_colocated()? == Some(true) {
+    ///     eprintln!("warning: the runtime and state directories share a filesystem");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(all(unix, feature = "runtime", feature = "state"))]
+    pub fn runtime_state_colocated(&self) -> io::Result<Option<bool>> {
+        use std::os::unix::fs::MetadataExt;
+        let (Some(runtime_dir), Some(state_dir)) = (self.runtime_dir(), self.state_dir()) else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            fs::metadata(runtime_dir)?.dev() == fs::metadata(state_dir)?.dev(),
+        ))
+    }
+
+    /// Returns the directories of all kinds that are world-writable without the sticky bit set.
+    ///
+    /// Such directories let any user on the system replace or remove the service's files, which is usually a security
+    /// issue. Directories that are world-writable but sticky, like `/tmp`, are not reported. The directories are
+    /// returned in the same order as [`Self::flat_iter`]. It performs a filesystem check for each directory every time
+    /// it is called.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata of any directory cannot be read, such as when it does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (kind, path) in dirs.insecure_dirs()? {
+    ///     eprintln!("warning: {kind} directory {} is world-writable", path.display());
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn insecure_dirs(&self) -> io::Result<Vec<(DirKind, PathBuf)>> {
+        use std::os::unix::fs::MetadataExt;
+        let mut insecure = Vec::new();
+        for (kind, _, dir) in self.flat_iter() {
+            let mode = fs::metadata(dir)?.mode();
+            if mode & 0o002 != 0 && mode & 0o1000 == 0 {
+                insecure.push((kind, dir.to_path_buf()));
+            }
+        }
+
+        Ok(insecure)
+    }
+
+    /// Returns the directories of all kinds that the current process is not permitted to access.
+    ///
+    /// This is a fast readiness check before failing deep in business logic. Each directory is checked by reading the
+    /// metadata of its `.` entry, which requires permission to traverse the directory itself as well as every parent,
+    /// and only failures with [`io::ErrorKind::PermissionDenied`] are reported. Directories that are missing or fail
+    /// for any other reason are not. The directories are returned in the same order as [`Self::flat_iter`]. It
+    /// performs a filesystem check for each directory every time it is called.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (kind, path) in dirs.inaccessible_dirs() {
+    ///     eprintln!("error: cannot access {kind} directory {}", path.display());
+    /// }
+    /// ```
+    pub fn inaccessible_dirs(&self) -> Vec<(DirKind, PathBuf)> {
+        self.flat_iter()
+            .filter(|(_, _, dir)| {
+                fs::metadata(dir.join("."))
+                    .is_err_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+            })
+            .map(|(kind, _, dir)| (kind, dir.to_path_buf()))
+            .collect()
+    }
+
+    /// Returns every path that is configured more than once, along with the kinds it is configured in.
+    ///
+    /// A path is a duplicate if it appears more than once within a kind, such as `RUNTIME_DIRECTORY=/run/a:/run/a`, or
+    /// in more than one kind, such as in both `RUNTIME_DIRECTORY` and `STATE_DIRECTORY`. Both usually indicate a
+    /// mistake in the unit file. Each kind is listed once per path, in the order of [`DirKind::ALL`], and the paths
+    /// are returned in the order they are first encountered by [`Self::flat_iter`]. Paths are compared exactly, without
+    /// normalization.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for (kinds, path) in dirs.duplicates() {
+    ///     eprintln!("warning: {} is configured more than once in {kinds:?}", path.display());
+    /// }
+    /// ```
+    pub fn duplicates(&self) -> Vec<(Vec<DirKind>, PathBuf)> {
+        let mut seen: Vec<(&Path, Vec<DirKind>, usize)> = Vec::new();
+        for (kind, _, path) in self.flat_iter() {
+            match seen.iter_mut().find(|(p, _, _)| *p == path) {
+                Some((_, kinds, count)) => {
+                    if !kinds.contains(&kind) {
+                        kinds.push(kind);
+                    }
+
+                    *count += 1;
+                }
+                None => seen.push((path, vec![kind], 1)),
+            }
+        }
+
+        seen.into_iter()
+            .filter(|(_, _, count)| *count > 1)
+            .map(|(path, kinds, _)| (kinds, path.to_path_buf()))
+            .collect()
+    }
+
+    /// Returns the directories of kind `a` that are not also directories of kind `b`.
+    ///
+    /// Paths are compared for equality with [`Path::eq`], component by component, and are returned in the order they
+    /// appear in `a`. This is useful to audit overlapping configurations alongside [`Self::duplicates`].
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// let runtime_only = dirs.kind_difference(DirKind::Runtime, DirKind::State);
+    /// ```
+    pub fn kind_difference(&self, a: DirKind, b: DirKind) -> Vec<&Path> {
+        let b = self.get(b);
+        self.as_paths(self.get(a))
+            .filter(|dir| !b.iter().any(|other| other == dir))
+            .collect()
+    }
+
+    /// Checks that no directory exceeds the given length or depth limits.
+    ///
+    /// As a hardening measure, this flags suspiciously long or deep paths. The length of a path is its length in bytes,
+    /// and its depth is its number of normal components, so `/run/foo` has a length of 8 and a depth of 2. This is
+    /// purely lexical: the filesystem is not accessed.
+    ///
+    /// # Errors
+    /// Returns every directory whose length exceeds `max_len` or whose depth exceeds `max_depth`, along with its kind,
+    /// in the order of [`Self::flat_iter`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if let Err(invalid) = dirs.validate_limits(4096, 32) {
+    ///     eprintln!("rejecting suspicious directories: {invalid:?}");
+    /// }
+    /// ```
+    pub fn validate_limits(
+        &self,
+        max_len: usize,
+        max_depth: usize,
+    ) -> Result<(), Vec<(DirKind, PathBuf)>> {
+        let invalid: Vec<_> = self
+            .flat_iter()
+            .filter(|(_, _, path)| {
+                let depth = path
+                    .components()
+                    .filter(|c| matches!(c, Component::Normal(_)))
+                    .count();
+
+                path.as_os_str().len() > max_len || depth > max_depth
+            })
+            .map(|(kind, _, path)| (kind, path.to_path_buf()))
+            .collect();
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+
+    /// Returns the kind of the configured directory that contains `path`.
+    ///
+    /// A directory contains `path` if `path` is the directory itself or a descendant of it, compared by component with
+    /// [`Path::starts_with`]. If `path` is contained by directories of several kinds, the first such kind in the order
+    /// of [`DirKind::ALL`] is returned. Returns [`None`] if no configured directory contains `path`. This is purely
+    /// lexical: the filesystem is not accessed.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// # std::env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+    /// let dirs = SystemdDirs::new();
+    /// assert_eq!(dirs.classify("/var/lib/foo/db.sqlite"), Some(DirKind::State));
+    /// ```
+    pub fn classify(&self, path: impl AsRef<Path>) -> Option<DirKind> {
+        let path = path.as_ref();
+        DirKind::ALL
+            .into_iter()
+            .find(|&kind| self.get(kind).iter().any(|dir| path.starts_with(dir)))
+    }
+
+    /// Returns whether every directory of every kind is `root` itself or a descendant of it.
+    ///
+    /// Directories are compared by component with [`Path::starts_with`], like [`Self::classify`], so `/srv/myappx` is
+    /// not under `/srv/myapp`. Kinds without directories do not affect the result, so this returns `true` if there
+    /// are no directories at all. This is purely lexical: the filesystem is not accessed, so `..` components and
+    /// symlinks are not resolved.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if !dirs.all_under("/srv/myapp") {
+    ///     eprintln!("refusing to run with directories outside of /srv/myapp");
+    /// }
+    /// ```
+    pub fn all_under(&self, root: impl AsRef<Path>) -> bool {
+        let root = root.as_ref();
+        self.flat_iter().all(|(_, _, dir)| dir.starts_with(root))
+    }
+
+    /// Returns `path` for display with the configured directory that contains it replaced by its environment variable,
+    /// such as `$STATE_DIRECTORY/db/foo.db`.
+    ///
+    /// This avoids leaking the filesystem layout into logs. The containing directory is chosen like
+    /// [`Self::classify`], with the first matching directory of that kind used. If no configured directory contains
+    /// `path`, it is returned unchanged. Paths that are not valid UTF-8 are converted lossily.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// # std::env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+    /// let dirs = SystemdDirs::new();
+    /// assert_eq!(dirs.display_relative("/var/lib/foo/db/foo.db"), "$STATE_DIRECTORY/db/foo.db");
+    /// ```
+    pub fn display_relative(&self, path: impl AsRef<Path>) -> String {
+        let path = path.as_ref();
+        let relative = self
+            .flat_iter()
+            .find_map(|(kind, _, dir)| path.strip_prefix(dir).ok().map(|rest| (kind, rest)));
+
+        match relative {
+            Some((kind, rest)) if rest.as_os_str().is_empty() => format!("${}", kind.env_key()),
+            Some((kind, rest)) => format!("${}/{}", kind.env_key(), rest.display()),
+            None => path.display().to_string(),
+        }
+    }
+
+    /// Returns a copy of the snapshot with every absolute directory re-anchored under `new_root`.
+    ///
+    /// This is useful for a supervisor outside of a chroot, where the directories seen by the service are relative to
+    /// the chroot's root: rebasing `/run/foo` under `/mnt/chroot` yields `/mnt/chroot/run/foo`. Relative directories
+    /// are left unchanged. This is purely lexical: the filesystem is not accessed.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new().with_runtime_dirs(vec![PathBuf::from("/run/foo")]);
+    /// let rebased = dirs.rebased("/mnt/chroot");
+    /// assert_eq!(rebased.runtime_dir(), Some(Path::new("/mnt/chroot/run/foo")));
+    /// ```
+    pub fn rebased(&self, new_root: impl AsRef<Path>) -> SystemdDirs {
+        let new_root = new_root.as_ref();
+        self.clone().map_dirs(|dir| {
+            if !dir.has_root() {
+                return dir.to_path_buf();
+            }
+
+            let relative = dir
+                .components()
+                .filter(|c| !matches!(c, Component::Prefix(_) | Component::RootDir));
+            new_root.join(relative.collect::<PathBuf>())
+        })
+    }
+
+    /// Returns the relative path from the first directory of kind `from` to the first directory of kind `to`.
+    ///
+    /// The path is computed by removing the components both directories share and replacing each remaining component of
+    /// the `from` directory with `..`, so `/var/lib/app` to `/var/cache/app` is `../../cache/app`. The same directory
+    /// yields `.`. This is purely lexical: the filesystem is not accessed, so `..` components and symlinks within the
+    /// directories are not resolved.
+    ///
+    /// Returns [`None`] if either kind has no directories, or if only one of the directories is absolute.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// let state_to_cache = dirs.relative_path(DirKind::State, DirKind::Cache);
+    /// ```
+    pub fn relative_path(&self, from: DirKind, to: DirKind) -> Option<PathBuf> {
+        relative_path(self.get(from).first()?, self.get(to).first()?)
+    }
+
+    /// Returns the kinds whose directories are not where the XDG Base Directory Specification would put them.
+    ///
+    /// When running as the user manager, systemd places each kind's directories under an XDG base directory:
+    /// `$XDG_RUNTIME_DIR`, `$XDG_STATE_HOME`, `$XDG_CACHE_HOME`, `$XDG_STATE_HOME/log`, and `$XDG_CONFIG_HOME`, using the
+    /// specification's defaults under `$HOME` when they are unset. A kind is reported if any of its directories is not
+    /// under the corresponding base, or if the base cannot be determined. Kinds without directories are never reported.
+    /// This helps explain why files ended up somewhere other than the XDG locations.
+    ///
+    /// The XDG variables are read from the current environment when this is called, not when the snapshot was taken.
+    /// The kinds are returned in the order of [`DirKind::ALL`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for kind in dirs.overrides_xdg() {
+    ///     println!("{} is not under its XDG base directory", kind.env_key());
+    /// }
+    /// ```
+    pub fn overrides_xdg(&self) -> Vec<DirKind> {
+        DirKind::ALL
+            .into_iter()
+            .filter(|&kind| {
+                let base = kind.xdg_base();
+                self.get(kind)
+                    .iter()
+                    .any(|dir| !base.as_ref().is_some_and(|base| dir.starts_with(base)))
+            })
+            .collect()
+    }
+
+    /// Returns the final path component shared by every directory across all kinds, which is usually the name of the
+    /// service.
+    ///
+    /// For example, with `RuntimeDirectory=myapp` and `StateDirectory=myapp`, the directories are `/run/myapp` and
+    /// `/var/lib/myapp`, so this returns `myapp`. This is a heuristic: it returns [`None`] if there are no directories,
+    /// if any final components disagree, or if the shared component is not valid UTF-8.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let name = dirs.inferred_name().unwrap_or_else(|| String::from("unknown"));
+    /// ```
+    pub fn inferred_name(&self) -> Option<String> {
+        let mut names = self.flat_iter().map(|(_, _, dir)| dir.file_name());
+        let name = names.next()??;
+        if !names.all(|other| other == Some(name)) {
+            return None;
+        }
+
+        name.to_str().map(String::from)
+    }
+
+    /// Returns `true` if no directories of any kind were set when [`SystemdDirs`] was created.
+    ///
+    /// This is a quick check for whether systemd configured any directories at all, such as when not running under
+    /// systemd.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// if dirs.is_empty() {
+    ///     eprintln!("not running under systemd");
+    /// }
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.total_dirs() == 0
+    }
+
+    /// Returns the total number of directories across all kinds.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// println!("{} directories configured", dirs.total_dirs());
+    /// ```
+    pub fn total_dirs(&self) -> usize {
+        DirKind::ALL
+            .into_iter()
+            .map(|kind| self.get(kind).len())
+            .sum()
+    }
+
+    /// Returns a fingerprint of the directories across all kinds, suitable as a cache key.
+    ///
+    /// The fingerprint is computed with 64-bit FNV-1a over the kinds in the order of [`DirKind::ALL`], including each
+    /// path's length and bytes. Unlike [`std::collections::hash_map::DefaultHasher`], this algorithm is fixed, so the
+    /// value is stable across runs and versions of this crate for the same directories on the same platform.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// println!("cache key: {:016x}", dirs.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        DirKind::ALL
+            .into_iter()
+            .fold(FNV_OFFSET_BASIS, |hash, kind| {
+                fnv1a_dirs(hash, self.get(kind))
+            })
+    }
+
+    /// Returns a short, filesystem-safe key derived from the cache directories as defined by `CacheDirectory` in the
+    /// unit file.
+    ///
+    /// The key is the 64-bit FNV-1a hash of the cache directories, computed like [`Self::fingerprint`] but ignoring
+    /// all other kinds, formatted as 16 lowercase hexadecimal digits. It is stable across runs and versions of this
+    /// crate for the same cache directories on the same platform, so it can name cache files deterministically per
+    /// configuration. Different configurations can collide, though this is unlikely.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let index = format!("index-{}.bin", dirs.cache_key());
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_key(&self) -> String {
+        format!("{:016x}", fnv1a_dirs(FNV_OFFSET_BASIS, &self.cache_dirs))
+    }
+
+    /// Returns the environment variables systemd would set for this snapshot as `(name, value)` pairs.
+    ///
+    /// Each value is the kind's directories joined with colons, exactly as systemd sets them. Kinds without directories
+    /// are skipped. The pairs can be passed to [`std::process::Command::envs`] to propagate the directories to a child
+    /// process.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::process::Command;
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let mut command = Command::new("child");
+    /// command.envs(dirs.to_env_pairs());
+    /// ```
+    pub fn to_env_pairs(&self) -> Vec<(&'static str, OsString)> {
+        DirKind::ALL
+            .into_iter()
+            .filter(|&kind| !self.get(kind).is_empty())
+            .map(|kind| (kind.env_key(), join_colons(self.get(kind))))
+            .collect()
+    }
+
+    /// Returns a POSIX shell script that exports the environment variables of [`Self::to_env_pairs`].
+    ///
+    /// Each line has the form `export RUNTIME_DIRECTORY='/run/a:/run/b'`. Values are single-quoted, with embedded single
+    /// quotes escaped as `'\''`, so the output can be passed to `eval` to reproduce the environment outside of
+    /// systemd. Kinds without directories are skipped, and paths that are not valid UTF-8 are converted lossily.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// print!("{}", dirs.to_shell_exports());
+    /// ```
+    pub fn to_shell_exports(&self) -> String {
+        self.to_env_pairs()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = value.to_string_lossy().replace('\'', r"'\''");
+                format!("export {key}='{value}'\n")
+            })
+            .collect()
+    }
+
+    /// Returns the environment variables of [`Self::to_env_pairs`] in the format of systemd's `EnvironmentFile=`.
+    ///
+    /// Each line has the form `RUNTIME_DIRECTORY=/run/a:/run/b`, without quoting or `export`, so the output can be
+    /// loaded by another unit or read back with [`Self::from_env_file`]. Kinds without directories are skipped, and
+    /// paths that are not valid UTF-8 are converted lossily. Since values are not quoted, paths with leading or
+    /// trailing whitespace, quotes, or backslashes are not preserved exactly when systemd reads the file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// print!("{}", dirs.to_environment_file());
+    /// ```
+    pub fn to_environment_file(&self) -> String {
+        self.to_env_pairs()
+            .into_iter()
+            .map(|(key, value)| format!("{key}={}\n", value.to_string_lossy()))
+            .collect()
+    }
+
+    /// Records a field on `span` for every kind that has directories.
+    ///
+    /// The field names are `runtime_dir`, `state_dir`, `cache_dir`, `logs_dir`, and `config_dir`, and each value is
+    /// the kind's directories joined with colons, converted lossily if they are not valid UTF-8. Kinds are recorded in
+    /// the order of [`DirKind::ALL`]. A span only records fields it was created with, so the caller must declare the
+    /// fields it wants as `tracing::field::Empty`; the others are ignored. This method is only available with the
+    /// `tracing` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// let span = tracing::info_span!(
+    ///     "service",
+    ///     runtime_dir = tracing::field::Empty,
+    ///     state_dir = tracing::field::Empty,
+    /// );
+    /// systemd_directories::SystemdDirs::new().record_fields(&span);
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn record_fields(&self, span: &tracing::Span) {
+        for kind in DirKind::ALL {
+            let dirs = self.get(kind);
+            if dirs.is_empty() {
+                continue;
+            }
+
+            let name = match kind {
+                DirKind::Runtime => "runtime_dir",
+                DirKind::State => "state_dir",
+                DirKind::Cache => "cache_dir",
+                DirKind::Logs => "logs_dir",
+                DirKind::Config => "config_dir",
+            };
+            span.record(name, join_colons(dirs).to_string_lossy().as_ref());
+        }
+    }
+
+    /// Creates every directory of every kind, including any missing parent directories.
+    ///
+    /// Hardened units may leave creating the directories to the service. This calls [`fs::create_dir_all`] for each
+    /// directory, kind by kind in the order of [`Self::kinds_by_priority`]. Kinds without directories are skipped, so
+    /// this is a no-op if no variables were set.
+    ///
+    /// # Errors
+    /// Returns the first error encountered while creating a directory. Directories before it have been created.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// dirs.create_all()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn create_all(&self) -> io::Result<()> {
+        for kind in Self::kinds_by_priority() {
+            for path in self.get(kind) {
+                fs::create_dir_all(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a stable, machine-readable description of the snapshot, one line per entry.
+    ///
+    /// Unlike the [`Debug`] output, this format is versioned and will not change within a major release, so it is
+    /// suitable for parsing by external tools. Version 1 of the format is:
+    ///
+    /// - A first line `version=1`.
+    /// - A line `<kind>=<dirs>` for every kind in the order of [`DirKind::ALL`], even those without directories, where
+    ///   `<kind>` is the [`Display`](fmt::Display) name of the kind and `<dirs>` its directories joined with colons.
+    ///   Paths that are not valid UTF-8 are converted lossily.
+    ///
+    /// # Examples
+    /// ```
+    /// # std::env::set_var("RUNTIME_DIRECTORY", "/run/a:/run/b");
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// let lines = dirs.to_debug_lines();
+    /// assert_eq!(lines[0], "version=1");
+    /// assert_eq!(lines[1], "runtime=/run/a:/run/b");
+    /// ```
+    pub fn to_debug_lines(&self) -> Vec<String> {
+        let mut lines = vec![String::from("version=1")];
+        for kind in DirKind::ALL {
+            let dirs = join_colons(self.get(kind));
+            lines.push(format!("{kind}={}", dirs.to_string_lossy()));
+        }
+
+        lines
+    }
+
+    /// Returns all kinds in the order they should be set up in: configuration, state, cache, logs, then runtime.
+    ///
+    /// Configuration comes first since the other directories may depend on it, followed by persistent data, then data
+    /// that can be regenerated, and finally ephemeral data. This order is stable and callers may rely on it. It is
+    /// used by [`Self::create_all`].
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// assert_eq!(SystemdDirs::kinds_by_priority()[0], DirKind::Config);
+    /// ```
+    pub fn kinds_by_priority() -> [DirKind; 5] {
+        [
+            DirKind::Config,
+            DirKind::State,
+            DirKind::Cache,
+            DirKind::Logs,
+            DirKind::Runtime,
+        ]
+    }
+
+    /// Returns an iterator over the kinds that have at least one directory, in the order of
+    /// [`Self::kinds_by_priority`].
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::new();
+    /// for kind in dirs.configured_kinds() {
+    ///     println!("{kind}: {:?}", dirs.get(kind));
+    /// }
+    /// ```
+    pub fn configured_kinds(&self) -> impl Iterator<Item = DirKind> + '_ {
+        Self::kinds_by_priority()
+            .into_iter()
+            .filter(|&kind| !self.get(kind).is_empty())
+    }
+
+    /// Sets the gauges describing the snapshot, as documented on [`Self::new`].
+    #[cfg(feature = "metrics")]
+    fn record_metrics(&self) {
+        let mut total = 0;
+        for kind in DirKind::ALL.into_iter().filter(DirKind::is_enabled) {
+            let count = self.get(kind).len();
+            total += count;
+            metrics::gauge!("systemd_directories_count", "kind" => kind.name()).set(count as f64);
+        }
+
+        metrics::gauge!("systemd_directories_total").set(total as f64);
+    }
+
+    /// Returns all directories of the given `kind` as a slice, without allocating.
+    ///
+    /// This is the primitive for code that is generic over the kind of directory. If the crate feature for `kind` is
+    /// disabled, it always returns an empty slice.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// for kind in DirKind::ALL {
+    ///     println!("{}: {:?}", kind.env_key(), dirs.get(kind));
+    /// }
+    /// ```
+    pub fn get(&self, kind: DirKind) -> &[PathBuf] {
+        match kind {
+            #[cfg(feature = "runtime")]
+            DirKind::Runtime => &self.runtime_dirs,
+            #[cfg(feature = "state")]
+            DirKind::State => &self.state_dirs,
+            #[cfg(feature = "cache")]
+            DirKind::Cache => &self.cache_dirs,
+            #[cfg(feature = "logs")]
+            DirKind::Logs => &self.logs_dirs,
+            #[cfg(feature = "config")]
+            DirKind::Config => &self.config_dirs,
+            #[allow(unreachable_patterns)]
+            _ => &[],
+        }
+    }
+
+    /// Returns all directories of the given `kind` as a slice, or why there are none.
+    ///
+    /// This behaves like [`Self::get`], but distinguishes a variable that was not set from one that was set but
+    /// contained no paths, in a single call.
+    ///
+    /// # Errors
+    /// Returns [`DirStatus::Unset`] if the variable for `kind` was not set, or if its crate feature is disabled, and
+    /// [`DirStatus::EmptyButPresent`] if it was set but contained no paths.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, DirStatus, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// match dirs.try_get(DirKind::State) {
+    ///     Ok(state_dirs) => println!("state directories: {state_dirs:?}"),
+    ///     Err(DirStatus::Unset) => println!("StateDirectory is not configured"),
+    ///     Err(DirStatus::EmptyButPresent) => eprintln!("warning: STATE_DIRECTORY is empty"),
+    /// }
+    /// ```
+    pub fn try_get(&self, kind: DirKind) -> Result<&[PathBuf], DirStatus> {
+        let dirs = self.get(kind);
+        if !dirs.is_empty() {
+            Ok(dirs)
+        } else if self.raw_segment_info(kind).is_some() {
+            Err(DirStatus::EmptyButPresent)
+        } else {
+            Err(DirStatus::Unset)
+        }
+    }
+
+    /// Returns the first directory suited for `purpose`, or [`None`] if there are no directories of its kind.
+    ///
+    /// This is equivalent to the first directory of [`Purpose::kind`], but makes the intent clear at the call site.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{Purpose, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// if let Some(dir) = dirs.dir_for_purpose(Purpose::PersistentData) {
+    ///     let database = dir.join("app.db");
+    ///     // --snip--
+    /// }
+    /// ```
+    pub fn dir_for_purpose(&self, purpose: Purpose) -> Option<&Path> {
+        self.get(purpose.kind()).first().map(PathBuf::as_path)
+    }
+
+    /// Returns the access modes of all kinds of directories, as defined by `RuntimeDirectoryMode` and its siblings in
+    /// the unit file.
+    ///
+    /// Like [`runtime_dir_mode`] and its siblings, the modes are parsed from the `*_DIRECTORY_MODE` environment
+    /// variables, which systemd does not export itself. They are read from the environment when this is called,
+    /// rather than when the snapshot was taken.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::DirKind;
+    /// let modes = systemd_directories::SystemdDirs::new().modes();
+    /// if let Some(mode) = modes.get(DirKind::State) {
+    ///     println!("state directory mode: {mode:04o}");
+    /// }
+    /// ```
+    pub fn modes(&self) -> DirModes {
+        let mode = |kind: DirKind| {
+            if !kind.is_enabled() {
+                return None;
+            }
+
+            parse_mode(&env::var(format!("{}_MODE", kind.env_key())).ok()?)
+        };
+
+        DirModes {
+            runtime: mode(DirKind::Runtime),
+            state: mode(DirKind::State),
+            cache: mode(DirKind::Cache),
+            logs: mode(DirKind::Logs),
+            config: mode(DirKind::Config),
+        }
+    }
+
+    /// Returns the access mode of the directories of `kind`, as returned by [`Self::modes`], with the file mode
+    /// creation mask of the current process applied.
+    ///
+    /// This is the mode a directory gets when this process creates it with the configured mode, such as with
+    /// [`std::fs::DirBuilder`]. Querying the mask with the `umask` system call requires setting it and restoring it,
+    /// which is racy in a multithreaded process and needs `unsafe` code, so the mask is instead read from the `Umask`
+    /// field of `/proc/self/status`, which Linux has provided since version 4.7.
+    ///
+    /// Returns [`None`] if the mode variable is unset or not a valid octal mode, or if the mask cannot be read. This
+    /// method is only available on Linux.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, SystemdDirs};
+    /// # std::env::set_var("STATE_DIRECTORY_MODE", "0777");
+    /// let dirs = SystemdDirs::new();
+    /// if let Some(mode) = dirs.effective_mode(DirKind::State) {
+    ///     println!("state directories are created with mode {mode:04o}");
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn effective_mode(&self, kind: DirKind) -> Option<u32> {
+        let mode = self.modes().get(kind)?;
+        let umask = parse_umask(&fs::read_to_string("/proc/self/status").ok()?)?;
+        Some(mode & !umask)
+    }
+
+    /// Returns whether the directories of `kind` survive a restart or stop of the service.
+    ///
+    /// This encodes the semantics documented in systemd.exec(5) rather than inspecting the file system. Runtime
+    /// directories depend on `RuntimeDirectoryPreserve`, as read from `RUNTIME_DIRECTORY_PRESERVE` when the snapshot
+    /// was created and parsed like [`runtime_dir_preserve`]: [`RuntimeDirectoryPreserve::Yes`] is
+    /// [`Persistence::Persistent`], [`RuntimeDirectoryPreserve::Restart`] is [`Persistence::UntilStop`] because the
+    /// directories are still removed when the unit stops, and anything else is [`Persistence::Ephemeral`]. All other
+    /// kinds are [`Persistence::Persistent`]. Note that runtime directories live on a tmpfs, so even preserved ones do
+    /// not survive a reboot.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::{DirKind, Persistence, SystemdDirs};
+    /// let dirs = SystemdDirs::new();
+    /// assert_eq!(dirs.persistence(DirKind::State), Persistence::Persistent);
+    /// ```
+    pub fn persistence(&self, kind: DirKind) -> Persistence {
+        match kind {
+            #[cfg(feature = "runtime")]
+            DirKind::Runtime => match self.runtime_preserve {
+                Some(RuntimeDirectoryPreserve::Yes) => Persistence::Persistent,
+                Some(RuntimeDirectoryPreserve::Restart) => Persistence::UntilStop,
+                Some(RuntimeDirectoryPreserve::No) | None => Persistence::Ephemeral,
+            },
+            #[cfg(not(feature = "runtime"))]
+            DirKind::Runtime => Persistence::Ephemeral,
+            DirKind::State | DirKind::Cache | DirKind::Logs | DirKind::Config => {
+                Persistence::Persistent
+            }
+        }
+    }
+
+    /// Returns a view of the snapshot shaped like `ProjectDirs` from the `directories` crate, or [`None`] if there are
+    /// no configuration, cache, or state directories.
+    ///
+    /// The configuration, cache, and data directories of `ProjectDirs` are always available, so like
+    /// `ProjectDirs::from`, this returns [`None`] when they cannot all be determined. The data directory maps to the
+    /// state directory, and the runtime directory is optional as it is in `ProjectDirs`.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// let dirs = SystemdDirs::new();
+    /// if let Some(project_dirs) = dirs.as_project_dirs_like() {
+    ///     let database = project_dirs.data_dir().join("app.db");
+    ///     // --snip--
+    /// }
+    /// ```
+    pub fn as_project_dirs_like(&self) -> Option<ProjectDirsLike<'_>> {
+        let first = |kind| self.get(kind).first().map(PathBuf::as_path);
+        Some(ProjectDirsLike {
+            config_dir: first(DirKind::Config)?,
+            cache_dir: first(DirKind::Cache)?,
+            data_dir: first(DirKind::State)?,
+            runtime_dir: first(DirKind::Runtime),
+        })
+    }
+
+    /// Helper to get all directories of the given `kind` mutably, or [`None`] if its feature is disabled.
+    fn get_mut(&mut self, kind: DirKind) -> Option<&mut Vec<PathBuf>> {
+        match kind {
+            #[cfg(feature = "runtime")]
+            DirKind::Runtime => Some(&mut self.runtime_dirs),
+            #[cfg(feature = "state")]
+            DirKind::State => Some(&mut self.state_dirs),
+            #[cfg(feature = "cache")]
+            DirKind::Cache => Some(&mut self.cache_dirs),
+            #[cfg(feature = "logs")]
+            DirKind::Logs => Some(&mut self.logs_dirs),
+            #[cfg(feature = "config")]
+            DirKind::Config => Some(&mut self.config_dirs),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Helper to map a slice of `PathBuf` to an iterator of `&Path`.
+    fn as_paths<'a>(&'a self, dirs: &'a [PathBuf]) -> impl Iterator<Item = &'a Path> + 'a {
+        dirs.iter().map(|p| p.as_path())
+    }
+}
+
+impl Default for SystemdDirs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for SystemdDirs {
+    fn eq(&self, other: &Self) -> bool {
+        DirKind::ALL
+            .into_iter()
+            .all(|kind| self.get(kind) == other.get(kind))
+    }
+}
+
+impl Eq for SystemdDirs {}
+
+/// A provider of systemd directories.
+///
+/// Code that is generic over this trait can be given a [`SystemdDirs`] in production and a fake in tests. The method
+/// of a kind only exists if the crate feature for that kind is enabled, so an implementation must define exactly the
+/// methods of the enabled kinds, such as by forwarding the features from its own crate.
+///
+/// # Examples
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use systemd_directories::{DirectoryProvider, SystemdDirs};
+///
+/// fn socket_path(dirs: &impl DirectoryProvider) -> Option<PathBuf> {
+///     dirs.runtime_dir().map(|dir| dir.join("app.sock"))
+/// }
+///
+/// struct MockDirs;
+///
+/// impl DirectoryProvider for MockDirs {
+///     #[cfg(feature = "runtime")]
+///     fn runtime_dir(&self) -> Option<&Path> {
+///         Some(Path::new("/tmp/mock"))
+///     }
+///
+///     #[cfg(feature = "state")]
+///     fn state_dir(&self) -> Option<&Path> {
+///         None
+///     }
+///
+///     #[cfg(feature = "cache")]
+///     fn cache_dir(&self) -> Option<&Path> {
+///         None
+///     }
+///
+///     #[cfg(feature = "logs")]
+///     fn logs_dir(&self) -> Option<&Path> {
+///         None
+///     }
+///
+///     #[cfg(feature = "config")]
+///     fn config_dir(&self) -> Option<&Path> {
+///         None
+///     }
+/// }
+///
+/// assert_eq!(socket_path(&MockDirs), Some(PathBuf::from("/tmp/mock/app.sock")));
+/// let real = socket_path(&SystemdDirs::new());
+/// ```
+pub trait DirectoryProvider {
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file.
+    #[cfg(feature = "runtime")]
+    fn runtime_dir(&self) -> Option<&Path>;
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file.
+    #[cfg(feature = "state")]
+    fn state_dir(&self) -> Option<&Path>;
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file.
+    #[cfg(feature = "cache")]
+    fn cache_dir(&self) -> Option<&Path>;
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file.
+    #[cfg(feature = "logs")]
+    fn logs_dir(&self) -> Option<&Path>;
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file.
+    #[cfg(feature = "config")]
+    fn config_dir(&self) -> Option<&Path>;
+}
+
+impl DirectoryProvider for SystemdDirs {
+    #[cfg(feature = "runtime")]
+    fn runtime_dir(&self) -> Option<&Path> {
+        SystemdDirs::runtime_dir(self)
+    }
+
+    #[cfg(feature = "state")]
+    fn state_dir(&self) -> Option<&Path> {
+        SystemdDirs::state_dir(self)
+    }
+
+    #[cfg(feature = "cache")]
+    fn cache_dir(&self) -> Option<&Path> {
+        SystemdDirs::cache_dir(self)
+    }
+
+    #[cfg(feature = "logs")]
+    fn logs_dir(&self) -> Option<&Path> {
+        SystemdDirs::logs_dir(self)
+    }
+
+    #[cfg(feature = "config")]
+    fn config_dir(&self) -> Option<&Path> {
+        SystemdDirs::config_dir(self)
+    }
+}
+
+/// A guard that sets the directory environment variables and restores their previous state when dropped.
+///
+/// This provides an RAII way to test code that uses the standalone functions or [`SystemdDirs::new`]. On creation,
+/// the five directory variables are set to the provided values, and any kind not provided is unset. On drop, each
+/// variable is restored to the value it had before, or unset if it was previously unset.
+///
+/// The environment is process-global, so tests that use [`EnvGuard`] concurrently must still be serialized, such as by
+/// holding a shared [`Mutex`](std::sync::Mutex) for the lifetime of the guard.
+///
+/// This type is only available with the `test-util` feature.
+///
+/// # Examples
+/// ```
+/// use systemd_directories::{DirKind, EnvGuard};
+/// {
+///     let _guard = EnvGuard::new([(DirKind::Runtime, "/run/foo")]);
+///     assert_eq!(systemd_directories::runtime_dir(), Some("/run/foo".into()));
+/// }
+/// ```
+#[cfg(feature = "test-util")]
+#[derive(Debug)]
+pub struct EnvGuard {
+    /// The value of each variable before the guard was created.
+    previous: Vec<(DirKind, Option<OsString>)>,
+}
+
+#[cfg(feature = "test-util")]
+impl EnvGuard {
+    /// Sets each kind's variable to its value in `vars`, unsetting the variables of all other kinds.
+    pub fn new<V: AsRef<OsStr>>(vars: impl IntoIterator<Item = (DirKind, V)>) -> Self {
+        let previous = DirKind::ALL
+            .into_iter()
+            .map(|kind| (kind, env::var_os(kind.env_key())))
+            .collect();
+
+        for kind in DirKind::ALL {
+            env::remove_var(kind.env_key());
+        }
+
+        for (kind, value) in vars {
+            env::set_var(kind.env_key(), value);
+        }
+
+        Self { previous }
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (kind, value) in &self.previous {
+            match value {
+                Some(value) => env::set_var(kind.env_key(), value),
+                None => env::remove_var(kind.env_key()),
+            }
+        }
+    }
+}
+
+/// Converts the snapshot into a map from each kind to its directories.
+///
+/// Only kinds with at least one directory are included in the map.
+impl From<SystemdDirs> for HashMap<DirKind, Vec<PathBuf>> {
+    fn from(mut dirs: SystemdDirs) -> Self {
+        DirKind::ALL
+            .into_iter()
+            .filter_map(|kind| Some((kind, mem::take(dirs.get_mut(kind)?))))
+            .filter(|(_, dirs)| !dirs.is_empty())
+            .collect()
+    }
+}
+
+/// Consumes the snapshot, yielding every directory of every kind in the order of
+/// [`SystemdDirs::kinds_by_priority`].
+///
+/// The directories are moved out of the snapshot without being cloned.
+///
+/// # Examples
+/// ```
+/// use std::path::PathBuf;
+/// let paths: Vec<PathBuf> = systemd_directories::SystemdDirs::new().into_iter().collect();
+/// ```
+impl IntoIterator for SystemdDirs {
+    type Item = PathBuf;
+    type IntoIter = iter::Flatten<array::IntoIter<Vec<PathBuf>, 5>>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        Self::kinds_by_priority()
+            .map(|kind| self.get_mut(kind).map(mem::take).unwrap_or_default())
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// Converts a reference to the snapshot into a map from each kind to its directories.
+///
+/// Only kinds with at least one directory are included in the map.
+impl<'a> From<&'a SystemdDirs> for HashMap<DirKind, Vec<&'a Path>> {
+    fn from(dirs: &'a SystemdDirs) -> Self {
+        DirKind::ALL
+            .into_iter()
+            .map(|kind| (kind, dirs.as_paths(dirs.get(kind)).collect::<Vec<_>>()))
+            .filter(|(_, dirs)| !dirs.is_empty())
+            .collect()
+    }
+}
+
+/// An ordered list of directories searched for files, like `PATH`, as returned by [`SystemdDirs::config_search_path`].
+///
+/// Earlier directories take precedence over later ones. Any list of directories can be searched by converting it from
+/// a [`Vec<PathBuf>`].
+///
+/// # Examples
+/// ```
+/// use std::path::PathBuf;
+/// use systemd_directories::SearchPath;
+/// let search_path = SearchPath::from(vec![PathBuf::from("/etc/myapp"), PathBuf::from("/usr/share/myapp")]);
+/// if let Some(config) = search_path.find("app.toml") {
+///     println!("loading {}", config.display());
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchPath {
+    /// The directories, in order of precedence.
+    dirs: Vec<PathBuf>,
+}
+
+impl SearchPath {
+    /// Returns the directories, in order of precedence.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Returns the path of the first file named `name` across the directories, or [`None`] if none of them contains
+    /// it.
+    ///
+    /// Only regular files, or symbolic links to them, match. This performs a filesystem check for each directory it
+    /// searches.
+    pub fn find(&self, name: impl AsRef<Path>) -> Option<PathBuf> {
+        let name = name.as_ref();
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    }
+}
+
+impl From<Vec<PathBuf>> for SearchPath {
+    fn from(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+}
+
+/// A builder for a [`SystemdDirs`] snapshot that configures how the environment is read, as returned by
+/// [`SystemdDirs::builder`].
+///
+/// Every option defaults to the behavior of [`SystemdDirs::new`].
+#[derive(Debug, Clone, Default)]
+pub struct SystemdDirsBuilder {
+    /// The options used to parse each variable.
+    options: ParseOptions,
+}
+
+impl SystemdDirsBuilder {
+    /// Sets whether a variable set to the empty string, such as `RUNTIME_DIRECTORY=""`, is treated as unset.
+    ///
+    /// By default, an empty variable counts as set, so it is reported by [`SystemdDirs::present_vars`],
+    /// [`SystemdDirs::check_empty`], and [`SystemdDirs::raw_segment_info`]. When `treat` is `true`, it is reported
+    /// like an unset variable instead. A variable that only contains separators, such as `:`, still counts as set.
+    /// Either way, an empty variable has no directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirs;
+    /// # std::env::set_var("RUNTIME_DIRECTORY", "");
+    /// let dirs = SystemdDirs::builder().treat_empty_as_unset(true).build();
+    /// assert!(dirs.present_vars().is_empty());
+    /// ```
+    pub fn treat_empty_as_unset(mut self, treat: bool) -> Self {
+        self.options.empty_is_unset = treat;
+        self
+    }
+
+    /// Returns a new [`SystemdDirs`] struct with a snapshot of the current environment, read according to the
+    /// options of the builder.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirs::builder().build();
+    /// assert_eq!(dirs, systemd_directories::SystemdDirs::new());
+    /// ```
+    pub fn build(&self) -> SystemdDirs {
+        let snapshot = env_snapshot();
+        SystemdDirs::from_lookup_with(|key| snapshot.get(key).cloned(), &self.options)
+    }
+}
+
+/// An error returned by [`SystemdDirsUtf8::new`] when a directory variable is not valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonUtf8Error {
+    /// The kind whose variable is not valid UTF-8.
+    kind: DirKind,
+}
+
+impl NonUtf8Error {
+    /// Returns the kind whose variable is not valid UTF-8.
+    pub fn kind(&self) -> DirKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for NonUtf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not valid UTF-8", self.kind.env_key())
+    }
+}
+
+impl Error for NonUtf8Error {}
+
+/// A snapshot of the environment like [`SystemdDirs`], but storing the directories as [`String`]s.
+///
+/// This is for services that only deal with UTF-8 paths and want `&str` access without converting each path.
+/// Construction fails if any directory variable is not valid UTF-8, so the accessors never need to.
+///
+/// # Examples
+/// ```
+/// use systemd_directories::SystemdDirsUtf8;
+/// let dirs = SystemdDirsUtf8::new()?;
+/// if let Some(runtime_dir) = dirs.runtime_dir() {
+///     println!("runtime directory: {runtime_dir}");
+/// }
+/// # Ok::<(), systemd_directories::NonUtf8Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemdDirsUtf8 {
+    /// All runtime directories when the struct was created.
+    #[cfg(feature = "runtime")]
+    runtime_dirs: Vec<String>,
+
+    /// All state directories when the struct was created.
+    #[cfg(feature = "state")]
+    state_dirs: Vec<String>,
+
+    /// All cache directories when the struct was created.
+    #[cfg(feature = "cache")]
+    cache_dirs: Vec<String>,
+
+    /// All logs directories when the struct was created.
+    #[cfg(feature = "logs")]
+    logs_dirs: Vec<String>,
+
+    /// All configuration directories when the struct was created.
+    #[cfg(feature = "config")]
+    config_dirs: Vec<String>,
+}
+
+impl SystemdDirsUtf8 {
+    /// Returns a new [`SystemdDirsUtf8`] struct with a snapshot of the current environment.
+    ///
+    /// The variables are parsed like [`SystemdDirs::new`], except that a variable that is not valid UTF-8 is an error
+    /// rather than being treated as empty.
+    ///
+    /// # Errors
+    /// Returns a [`NonUtf8Error`] for the first variable that is not valid UTF-8, in the order of [`DirKind::ALL`].
+    ///
+    /// # Examples
+    /// ```
+    /// use systemd_directories::SystemdDirsUtf8;
+    /// let dirs = SystemdDirsUtf8::new()?;
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    pub fn new() -> Result<Self, NonUtf8Error> {
+        #[cfg_attr(
+            not(any(
+                feature = "runtime",
+                feature = "state",
+                feature = "cache",
+                feature = "logs",
+                feature = "config"
+            )),
+            allow(unused_variables)
+        )]
+        let dirs = |kind: DirKind| -> Result<Vec<String>, NonUtf8Error> {
+            let value = env::var_os(kind.env_key()).unwrap_or_default();
+            let value = value.into_string().map_err(|_| NonUtf8Error { kind })?;
+            Ok(ColonSeparatedPaths::new(value)
+                .parse(&ParseOptions::default())
+                .into_iter()
+                .filter_map(|dir| dir.into_os_string().into_string().ok())
+                .collect())
+        };
+
+        Ok(Self {
+            #[cfg(feature = "runtime")]
+            runtime_dirs: dirs(DirKind::Runtime)?,
+            #[cfg(feature = "state")]
+            state_dirs: dirs(DirKind::State)?,
+            #[cfg(feature = "cache")]
+            cache_dirs: dirs(DirKind::Cache)?,
+            #[cfg(feature = "logs")]
+            logs_dirs: dirs(DirKind::Logs)?,
+            #[cfg(feature = "config")]
+            config_dirs: dirs(DirKind::Config)?,
+        })
+    }
+
+    /// Returns the first runtime directory as defined by `RuntimeDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let runtime_dir: Option<&str> = dirs.runtime_dir();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dir(&self) -> Option<&str> {
+        self.runtime_dirs.first().map(String::as_str)
+    }
+
+    /// Returns all runtime directories as defined by `RuntimeDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let runtime_dirs: Vec<&str> = dirs.runtime_dirs();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn runtime_dirs(&self) -> Vec<&str> {
+        self.runtime_dirs.iter().map(String::as_str).collect()
+    }
+
+    /// Returns the first state directory as defined by `StateDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let state_dir: Option<&str> = dirs.state_dir();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dir(&self) -> Option<&str> {
+        self.state_dirs.first().map(String::as_str)
+    }
+
+    /// Returns all state directories as defined by `StateDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let state_dirs: Vec<&str> = dirs.state_dirs();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "state")]
+    pub fn state_dirs(&self) -> Vec<&str> {
+        self.state_dirs.iter().map(String::as_str).collect()
+    }
+
+    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let cache_dir: Option<&str> = dirs.cache_dir();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dir(&self) -> Option<&str> {
+        self.cache_dirs.first().map(String::as_str)
+    }
+
+    /// Returns all cache directories as defined by `CacheDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let cache_dirs: Vec<&str> = dirs.cache_dirs();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_dirs(&self) -> Vec<&str> {
+        self.cache_dirs.iter().map(String::as_str).collect()
+    }
+
+    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let logs_dir: Option<&str> = dirs.logs_dir();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dir(&self) -> Option<&str> {
+        self.logs_dirs.first().map(String::as_str)
+    }
+
+    /// Returns all logs directories as defined by `LogsDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let logs_dirs: Vec<&str> = dirs.logs_dirs();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "logs")]
+    pub fn logs_dirs(&self) -> Vec<&str> {
+        self.logs_dirs.iter().map(String::as_str).collect()
+    }
+
+    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let config_dir: Option<&str> = dirs.config_dir();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dir(&self) -> Option<&str> {
+        self.config_dirs.first().map(String::as_str)
+    }
+
+    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file.
+    ///
+    /// # Examples
+    /// ```
+    /// let dirs = systemd_directories::SystemdDirsUtf8::new()?;
+    /// let config_dirs: Vec<&str> = dirs.config_dirs();
+    /// # Ok::<(), systemd_directories::NonUtf8Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_dirs(&self) -> Vec<&str> {
+        self.config_dirs.iter().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Serializes tests that read or modify the process environment.
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Acquires [`ENV_LOCK`], ignoring poisoning from a previously failed test.
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    fn lock_env() -> MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A uniquely-named directory under [`env::temp_dir`] that is removed on drop.
+    struct TempDir {
+        /// The path of the directory.
+        path: PathBuf,
+    }
+
+    impl TempDir {
+        /// Creates a new, empty temporary directory.
+        fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "systemd-directories-{}-{}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            fs::create_dir_all(&path).unwrap();
+            Self { path }
+        }
+
+        /// Returns the path of the directory.
+        fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    /// Creates a read-only directory named `name` in `parent`.
+    ///
+    /// Returns [`None`] if the current user can write to it anyway, such as when running as root, in which case tests
+    /// relying on permission errors cannot run.
+    #[cfg(all(
+        unix,
+        any(
+            feature = "cache",
+            feature = "config",
+            all(feature = "runtime", feature = "state")
+        )
+    ))]
+    fn read_only_dir(parent: &Path, name: &str) -> Option<PathBuf> {
+        use std::os::unix::fs::PermissionsExt;
+        let path = parent.join(name);
+        fs::create_dir(&path).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o555)).unwrap();
+        if fs::create_dir(path.join("probe")).is_ok() {
+            return None;
+        }
+
+        Some(path)
+    }
+
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    fn test_set(
+        env_key: &str,
+        dirs: &[&str],
+        standalone_single: fn() -> Option<PathBuf>,
+        standalone_all: fn() -> Vec<PathBuf>,
+        method_single: fn(&SystemdDirs) -> Option<&Path>,
+        method_all: fn(&SystemdDirs) -> Vec<&Path>,
+    ) {
+        env::set_var(env_key, dirs.join(":"));
+        assert_eq!(standalone_single(), Some(PathBuf::from(dirs[0])));
+        assert_eq!(
+            standalone_all(),
+            dirs.iter().map(PathBuf::from).collect::<Vec<PathBuf>>()
+        );
+        let systemd_dirs = SystemdDirs::new();
+        assert_eq!(method_single(&systemd_dirs), Some(Path::new(dirs[0])));
+        assert_eq!(
+            method_all(&systemd_dirs),
+            dirs.iter().map(Path::new).collect::<Vec<&Path>>()
+        );
+    }
+
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    fn test_unset(
+        env_key: &str,
+        standalone_single: fn() -> Option<PathBuf>,
+        standalone_all: fn() -> Vec<PathBuf>,
+        method_single: fn(&SystemdDirs) -> Option<&Path>,
+        method_all: fn(&SystemdDirs) -> Vec<&Path>,
+    ) {
+        env::remove_var(env_key);
+        assert_eq!(standalone_single(), None);
+        assert!(standalone_all().is_empty());
+        let systemd_dirs = SystemdDirs::new();
+        assert_eq!(method_single(&systemd_dirs), None);
+        assert!(method_all(&systemd_dirs).is_empty());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_directory() {
+        let _env = lock_env();
+        test_set(
+            "RUNTIME_DIRECTORY",
+            &["/run/foo", "/run/bar"],
+            runtime_dir,
+            runtime_dirs,
+            SystemdDirs::runtime_dir,
+            SystemdDirs::runtime_dirs,
+        );
+
+        test_unset(
+            "RUNTIME_DIRECTORY",
+            runtime_dir,
+            runtime_dirs,
+            SystemdDirs::runtime_dir,
+            SystemdDirs::runtime_dirs,
+        );
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_state_directory() {
+        let _env = lock_env();
+        test_set(
+            "STATE_DIRECTORY",
+            &["/var/lib/foo", "/var/lib/bar"],
+            state_dir,
+            state_dirs,
+            SystemdDirs::state_dir,
+            SystemdDirs::state_dirs,
+        );
+
+        test_unset(
+            "STATE_DIRECTORY",
+            state_dir,
+            state_dirs,
+            SystemdDirs::state_dir,
+            SystemdDirs::state_dirs,
+        );
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_cache_directory() {
+        let _env = lock_env();
+        test_set(
+            "CACHE_DIRECTORY",
+            &["/var/cache/foo", "/var/cache/bar"],
+            cache_dir,
+            cache_dirs,
+            SystemdDirs::cache_dir,
+            SystemdDirs::cache_dirs,
+        );
+
+        test_unset(
+            "CACHE_DIRECTORY",
+            cache_dir,
+            cache_dirs,
+            SystemdDirs::cache_dir,
+            SystemdDirs::cache_dirs,
+        );
+    }
+
+    #[cfg(feature = "logs")]
+    #[test]
+    fn test_logs_directory() {
+        let _env = lock_env();
+        test_set(
+            "LOGS_DIRECTORY",
+            &["/var/log/foo", "/var/log/bar"],
+            logs_dir,
+            logs_dirs,
+            SystemdDirs::logs_dir,
+            SystemdDirs::logs_dirs,
+        );
+
+        test_unset(
+            "LOGS_DIRECTORY",
+            logs_dir,
+            logs_dirs,
+            SystemdDirs::logs_dir,
+            SystemdDirs::logs_dirs,
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_configuration_directory() {
+        let _env = lock_env();
+        test_set(
+            "CONFIGURATION_DIRECTORY",
+            &["/etc/foo", "/etc/bar"],
+            config_dir,
+            config_dirs,
+            SystemdDirs::config_dir,
+            SystemdDirs::config_dirs,
+        );
+
+        test_unset(
+            "CONFIGURATION_DIRECTORY",
+            config_dir,
+            config_dirs,
+            SystemdDirs::config_dir,
+            SystemdDirs::config_dirs,
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_dirs_existing() {
+        let _env = lock_env();
+        let existing = TempDir::new();
+        let missing = existing.path().join("missing");
+        env::set_var(
+            "RUNTIME_DIRECTORY",
+            format!("{}:{}", missing.display(), existing.path().display()),
+        );
+
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.runtime_dirs().len(), 2);
+        assert_eq!(dirs.runtime_dirs_existing(), vec![existing.path()]);
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_from_env_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/systemd.env");
+        let dirs = SystemdDirs::from_env_file(path).unwrap();
+        assert_eq!(
+            dirs.runtime_dirs(),
+            vec![Path::new("/run/foo"), Path::new("/run/bar")]
+        );
+        assert_eq!(dirs.state_dirs(), vec![Path::new("/var/lib/foo")]);
+        assert_eq!(dirs.cache_dirs(), vec![Path::new("/var/cache/foo")]);
+        assert_eq!(dirs.logs_dirs(), vec![Path::new("/var/log/foo bar")]);
+        assert!(dirs.config_dirs().is_empty());
+    }
+
+    #[test]
+    fn test_from_env_file_malformed() {
+        let dir = TempDir::new();
+        let path = dir.path().join("malformed.env");
+        fs::write(&path, "RUNTIME_DIRECTORY=/run/foo\nnot a variable\n").unwrap();
+        let err = SystemdDirs::from_env_file(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_dir_relative_to() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/myapp/sub:/run/other");
+        assert_eq!(
+            runtime_dir_relative_to("/run"),
+            Some(PathBuf::from("myapp/sub"))
+        );
+        assert_eq!(
+            runtime_dir_relative_to("/run/myapp/"),
+            Some(PathBuf::from("sub"))
+        );
+        assert_eq!(runtime_dir_relative_to("/var/lib"), None);
+        assert_eq!(runtime_dir_relative_to("/run/my"), None);
+
+        env::remove_var("RUNTIME_DIRECTORY");
+        assert_eq!(runtime_dir_relative_to("/run"), None);
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_dir_typed() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo:/var/lib/bar");
+        let dirs = SystemdDirs::new();
+        env::remove_var("STATE_DIRECTORY");
+
+        let state_dir = dirs.state_dir_typed().unwrap();
+        assert_eq!(state_dir.kind(), DirKind::State);
+        assert_eq!(state_dir.as_path(), Path::new("/var/lib/foo"));
+        assert_eq!(state_dir.into_path_buf(), PathBuf::from("/var/lib/foo"));
+        assert_eq!(SystemdDirs::new().state_dir_typed(), None);
+    }
+
+    #[test]
+    fn test_systemd_dir_deref_as_ref() {
+        fn takes_as_ref(path: impl AsRef<Path>) -> PathBuf {
+            path.as_ref().to_path_buf()
+        }
+
+        let dir = SystemdDir::new(DirKind::Runtime, "/run/foo");
+        assert_eq!(dir.join("sock"), PathBuf::from("/run/foo/sock"));
+        assert_eq!(dir.file_name(), Some("foo".as_ref()));
+        assert_eq!(&*dir, Path::new("/run/foo"));
+        assert_eq!(takes_as_ref(&dir), PathBuf::from("/run/foo"));
+        assert_eq!(takes_as_ref(dir), PathBuf::from("/run/foo"));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_dirs_common_root() {
+        let _env = lock_env();
+        let common_root = |value: &str| {
+            env::set_var("RUNTIME_DIRECTORY", value);
+            SystemdDirs::new().runtime_dirs_common_root()
+        };
+
+        assert_eq!(
+            common_root("/run/a/b:/run/a/c"),
+            Some(PathBuf::from("/run/a"))
+        );
+        assert_eq!(common_root("/run/ab:/run/ac"), Some(PathBuf::from("/run")));
+        assert_eq!(common_root("/run/a/b"), Some(PathBuf::from("/run/a/b")));
+        assert_eq!(common_root("/run/a:/var/lib/a"), None);
+        assert_eq!(common_root("/run/a:relative/a"), None);
+        assert_eq!(common_root(""), None);
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_new_normalized() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo/:/run/./foo:/run//foo");
+        env::set_var("STATE_DIRECTORY", "./foo/../bar/.:.");
+        let dirs = SystemdDirs::new_normalized();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(dirs.runtime_dirs(), vec![Path::new("/run/foo"); 3]);
+        assert_eq!(
+            dirs.state_dirs(),
+            vec![Path::new("foo/../bar"), Path::new(".")]
+        );
+    }
+
+    #[cfg(all(feature = "cache", feature = "logs"))]
+    #[test]
+    fn test_check_empty() {
+        let _env = lock_env();
+        env::set_var("CACHE_DIRECTORY", ":");
+        env::set_var("LOGS_DIRECTORY", "");
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        env::remove_var("RUNTIME_DIRECTORY");
+        let dirs = SystemdDirs::new();
+        env::remove_var("CACHE_DIRECTORY");
+        env::remove_var("LOGS_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert!(dirs.cache_dirs().is_empty());
+        assert_eq!(dirs.check_empty(), vec![DirKind::Cache, DirKind::Logs]);
+        assert!(SystemdDirs::new().check_empty().is_empty());
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_changed_since() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        let before = SystemdDirs::new();
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo:/var/lib/bar");
+        let after = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(after.changed_since(&before), vec![DirKind::State]);
+        assert_eq!(before.changed_since(&after), vec![DirKind::State]);
+        assert!(after.changed_since(&after).is_empty());
+    }
+
+    #[cfg(feature = "logs")]
+    #[test]
+    fn test_dir_os() {
+        let _env = lock_env();
+        env::set_var("LOGS_DIRECTORY", "::/var/log/foo:/var/log/bar");
+        assert_eq!(logs_dir_os(), Some(OsString::from("/var/log/foo")));
+        env::set_var("LOGS_DIRECTORY", ":");
+        assert_eq!(logs_dir_os(), None);
+        env::remove_var("LOGS_DIRECTORY");
+        assert_eq!(logs_dir_os(), None);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_os_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let _env = lock_env();
+        let value = OsStr::from_bytes(b":/run/f\xffo:/run/bar");
+        env::set_var("RUNTIME_DIRECTORY", value);
+        assert_eq!(
+            runtime_dir_os(),
+            Some(OsStr::from_bytes(b"/run/f\xffo").to_os_string())
+        );
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(all(feature = "runtime", feature = "config"))]
+    #[test]
+    fn test_into_hash_map() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CONFIGURATION_DIRECTORY");
+
+        let borrowed = HashMap::<DirKind, Vec<&Path>>::from(&dirs);
+        assert_eq!(
+            borrowed,
+            HashMap::from([
+                (
+                    DirKind::Runtime,
+                    vec![Path::new("/run/foo"), Path::new("/run/bar")]
+                ),
+                (DirKind::Config, vec![Path::new("/etc/foo")]),
+            ])
+        );
+
+        let owned = HashMap::<DirKind, Vec<PathBuf>>::from(dirs);
+        assert_eq!(
+            owned,
+            HashMap::from([
+                (
+                    DirKind::Runtime,
+                    vec![PathBuf::from("/run/foo"), PathBuf::from("/run/bar")]
+                ),
+                (DirKind::Config, vec![PathBuf::from("/etc/foo")]),
+            ])
+        );
+    }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[test]
+    fn test_new_strict() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        let dirs = SystemdDirs::new_strict().unwrap();
+        assert_eq!(dirs.state_dirs(), vec![Path::new("/var/lib/foo")]);
+
+        env::set_var("CACHE_DIRECTORY", "::");
+        let err = SystemdDirs::new_strict().unwrap_err();
+        assert_eq!(err, SystemdDirsError::Empty(DirKind::Cache));
+        assert_eq!(err.kind(), DirKind::Cache);
+        assert_eq!(err.to_string(), "CACHE_DIRECTORY is set but empty");
+
+        env::remove_var("STATE_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+    }
+
+    #[cfg(feature = "logs")]
+    #[cfg(unix)]
+    #[test]
+    fn test_new_strict_not_unicode() {
+        use std::os::unix::ffi::OsStrExt;
+        let _env = lock_env();
+        env::set_var("LOGS_DIRECTORY", OsStr::from_bytes(b"/var/log/f\xffo"));
+        let err = SystemdDirs::new_strict().unwrap_err();
+        env::remove_var("LOGS_DIRECTORY");
+
+        assert_eq!(err, SystemdDirsError::NotUnicode(DirKind::Logs));
+        assert_eq!(err.to_string(), "LOGS_DIRECTORY is not valid UTF-8");
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_with_system_defaults() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", "/srv/myapp");
+        env::set_var("CACHE_DIRECTORY", ":");
+        let dirs = SystemdDirs::with_system_defaults("myapp");
+        env::remove_var("STATE_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+
+        assert_eq!(dirs.runtime_dirs(), vec![Path::new("/run/myapp")]);
+        assert_eq!(dirs.state_dirs(), vec![Path::new("/srv/myapp")]);
+        assert_eq!(dirs.cache_dirs(), vec![Path::new("/var/cache/myapp")]);
+        assert_eq!(dirs.logs_dirs(), vec![Path::new("/var/log/myapp")]);
+        assert_eq!(dirs.config_dirs(), vec![Path::new("/etc/myapp")]);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_flat_iter() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo:/var/lib/bar");
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar:/run/baz");
+        let dirs = SystemdDirs::new();
+        env::remove_var("STATE_DIRECTORY");
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        assert_eq!(
+            dirs.flat_iter().collect::<Vec<_>>(),
+            vec![
+                (DirKind::Runtime, 0, Path::new("/run/foo")),
+                (DirKind::Runtime, 1, Path::new("/run/bar")),
+                (DirKind::Runtime, 2, Path::new("/run/baz")),
+                (DirKind::State, 0, Path::new("/var/lib/foo")),
+                (DirKind::State, 1, Path::new("/var/lib/bar")),
+            ]
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_dir_preserve() {
+        let _env = lock_env();
+        let preserve = |value: &str| {
+            env::set_var("RUNTIME_DIRECTORY_PRESERVE", value);
+            runtime_dir_preserve()
+        };
+
+        for value in ["yes", "y", "true", "t", "on", "1", "YES", "True"] {
+            assert_eq!(
+                preserve(value),
+                Some(RuntimeDirectoryPreserve::Yes),
+                "{value}"
+            );
+        }
+
+        for value in ["no", "n", "false", "f", "off", "0", "NO", "False"] {
+            assert_eq!(
+                preserve(value),
+                Some(RuntimeDirectoryPreserve::No),
+                "{value}"
+            );
+        }
+
+        assert_eq!(preserve("restart"), Some(RuntimeDirectoryPreserve::Restart));
+        assert_eq!(preserve("Restart"), Some(RuntimeDirectoryPreserve::Restart));
+        assert_eq!(preserve("maybe"), None);
+        assert_eq!(preserve(""), None);
+
+        env::remove_var("RUNTIME_DIRECTORY_PRESERVE");
+        assert_eq!(runtime_dir_preserve(), None);
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_dir_first_existing() {
+        let _env = lock_env();
+        let existing = TempDir::new();
+        let missing = existing.path().join("missing");
+        env::set_var(
+            "CACHE_DIRECTORY",
+            format!("{}:{}", missing.display(), existing.path().display()),
+        );
+
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.cache_dir(), Some(missing.as_path()));
+        assert_eq!(dirs.cache_dir_first_existing(), Some(existing.path()));
+
+        env::set_var("CACHE_DIRECTORY", missing);
+        assert_eq!(SystemdDirs::new().cache_dir_first_existing(), None);
+        env::remove_var("CACHE_DIRECTORY");
+        assert_eq!(SystemdDirs::new().cache_dir_first_existing(), None);
+    }
+
+    #[cfg(feature = "state")]
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_string_lossy() {
+        use std::os::unix::ffi::OsStrExt;
+        let _env = lock_env();
+        env::set_var(
+            "STATE_DIRECTORY",
+            OsStr::from_bytes(b"/var/lib/f\xffo:/var/lib/bar"),
+        );
+        assert_eq!(
+            state_dir_string_lossy(),
+            Some(String::from("/var/lib/f\u{FFFD}o"))
+        );
+
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        assert_eq!(state_dir_string_lossy(), Some(String::from("/var/lib/foo")));
+        env::remove_var("STATE_DIRECTORY");
+        assert_eq!(state_dir_string_lossy(), None);
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_with_dirs() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/foo");
+        env::set_var("LOGS_DIRECTORY", "/var/log/foo");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let original = SystemdDirs::new();
+        for kind in DirKind::ALL {
+            env::remove_var(kind.env_key());
+        }
+
+        let dirs = original
+            .clone()
+            .with_cache_dirs(vec![PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")]);
+
+        assert_eq!(
+            dirs.cache_dirs(),
+            vec![Path::new("/tmp/a"), Path::new("/tmp/b")]
+        );
+        assert_eq!(dirs.changed_since(&original), vec![DirKind::Cache]);
+        assert_eq!(dirs.raw_segment_info(DirKind::Cache), Some((2, 2)));
+        assert_eq!(dirs.runtime_dirs(), original.runtime_dirs());
+        assert_eq!(dirs.state_dirs(), original.state_dirs());
+        assert_eq!(dirs.logs_dirs(), original.logs_dirs());
+        assert_eq!(dirs.config_dirs(), original.config_dirs());
+
+        let cleared = original.with_cache_dirs(Vec::new());
+        assert_eq!(cleared.raw_segment_info(DirKind::Cache), Some((0, 0)));
+        assert_eq!(cleared.check_empty(), vec![DirKind::Cache]);
+
+        let added = SystemdDirs::from_lookup(|_| None).with_state_dirs(vec![PathBuf::from("/a")]);
+        assert_eq!(added.present_vars(), vec![DirKind::State]);
+        assert_eq!(added.raw_segment_info(DirKind::State), Some((1, 1)));
+        let added = added.with_runtime_dirs(vec![PathBuf::new(), PathBuf::from("/run/a")]);
+        assert_eq!(added.present_vars(), vec![DirKind::Runtime, DirKind::State]);
+        assert_eq!(added.raw_segment_info(DirKind::Runtime), Some((2, 1)));
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_duplicates() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/srv/a:/run/b:/srv/a:/run/c:/run/c");
+        env::set_var("STATE_DIRECTORY", "/var/lib/a:/srv/a");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(
+            dirs.duplicates(),
+            vec![
+                (
+                    vec![DirKind::Runtime, DirKind::State],
+                    PathBuf::from("/srv/a")
+                ),
+                (vec![DirKind::Runtime], PathBuf::from("/run/c")),
+            ]
+        );
+        assert!(SystemdDirs::new().duplicates().is_empty());
+    }
+
+    #[cfg(feature = "logs")]
+    #[test]
+    fn test_dirs_sorted() {
+        let _env = lock_env();
+        env::set_var(
+            "LOGS_DIRECTORY",
+            "/var/log/c:/var/log/a:/var/log/c:/var/log/b",
+        );
+        let dirs = SystemdDirs::new();
+        env::remove_var("LOGS_DIRECTORY");
+
+        assert_eq!(
+            dirs.logs_dirs_sorted(),
+            vec![
+                Path::new("/var/log/a"),
+                Path::new("/var/log/b"),
+                Path::new("/var/log/c")
+            ]
+        );
+        assert_eq!(
+            dirs.logs_dirs(),
+            vec![
+                Path::new("/var/log/c"),
+                Path::new("/var/log/a"),
+                Path::new("/var/log/c"),
+                Path::new("/var/log/b")
+            ]
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_preserving_empty() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", ":a::b");
+        let filtered = SystemdDirs::new();
+        let preserving = SystemdDirs::new_preserving_empty();
+
+        assert_eq!(
+            filtered.runtime_dirs(),
+            vec![Path::new("a"), Path::new("b")]
+        );
+        assert_eq!(runtime_dirs(), vec![PathBuf::from("a"), PathBuf::from("b")]);
+        assert_eq!(
+            preserving.runtime_dirs(),
+            vec![Path::new(""), Path::new("a"), Path::new(""), Path::new("b")]
+        );
+        assert_eq!(
+            runtime_dirs_preserving_empty(),
+            vec![
+                PathBuf::new(),
+                PathBuf::from("a"),
+                PathBuf::new(),
+                PathBuf::from("b")
+            ]
+        );
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[test]
+    fn test_classify() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        env::set_var("CACHE_DIRECTORY", "/var/lib/foo/cache:/var/cache/foo");
+        let dirs = SystemdDirs::new();
+        env::remove_var("STATE_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+
+        assert_eq!(dirs.classify("/var/lib/foo"), Some(DirKind::State));
+        assert_eq!(dirs.classify("/var/lib/foo/db"), Some(DirKind::State));
+        assert_eq!(dirs.classify("/var/cache/foo/x"), Some(DirKind::Cache));
+        assert_eq!(dirs.classify("/var/lib/foo/cache/x"), Some(DirKind::State));
+        assert_eq!(dirs.classify("/var/lib/foobar"), None);
+        assert_eq!(dirs.classify("/etc/foo"), None);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "logs"))]
+    #[test]
+    fn test_is_empty_total_dirs() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::new();
+        assert!(dirs.is_empty());
+        assert_eq!(dirs.total_dirs(), 0);
+
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("LOGS_DIRECTORY", "/var/log/foo");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("LOGS_DIRECTORY");
+        assert!(!dirs.is_empty());
+        assert_eq!(dirs.total_dirs(), 3);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "config"))]
+    #[test]
+    fn test_to_env_pairs() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CONFIGURATION_DIRECTORY");
+
+        let pairs = dirs.to_env_pairs();
+        assert_eq!(
+            pairs,
+            vec![
+                ("RUNTIME_DIRECTORY", OsString::from("/run/foo:/run/bar")),
+                ("CONFIGURATION_DIRECTORY", OsString::from("/etc/foo")),
+            ]
+        );
+
+        let mut command = process::Command::new("true");
+        command.envs(pairs);
+        let mut envs = command.get_envs().collect::<Vec<_>>();
+        envs.sort();
+        assert_eq!(
+            envs,
+            vec![
+                (
+                    OsStr::new("CONFIGURATION_DIRECTORY"),
+                    Some(OsStr::new("/etc/foo"))
+                ),
+                (
+                    OsStr::new("RUNTIME_DIRECTORY"),
+                    Some(OsStr::new("/run/foo:/run/bar"))
+                ),
+            ]
+        );
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_dir_buf() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/foo");
+        env::set_var("LOGS_DIRECTORY", "/var/log/foo");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.runtime_dir_buf(), runtime_dir());
+        assert_eq!(dirs.state_dir_buf(), state_dir());
+        assert_eq!(dirs.cache_dir_buf(), cache_dir());
+        assert_eq!(dirs.logs_dir_buf(), logs_dir());
+        assert_eq!(dirs.config_dir_buf(), config_dir());
+
+        for kind in DirKind::ALL {
+            env::remove_var(kind.env_key());
+        }
+
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.runtime_dir_buf(), runtime_dir());
+        assert_eq!(dirs.config_dir_buf(), None);
+    }
+
+    #[cfg(all(
+        feature = "test-util",
+        feature = "runtime",
+        feature = "state",
+        feature = "cache"
+    ))]
+    #[test]
+    fn test_env_guard() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/original");
+        env::set_var("STATE_DIRECTORY", "/var/lib/original");
+        env::remove_var("CACHE_DIRECTORY");
+
+        {
+            let _guard = EnvGuard::new([
+                (DirKind::Runtime, "/run/foo:/run/bar"),
+                (DirKind::Cache, "/var/cache/foo"),
+            ]);
+
+            assert_eq!(
+                runtime_dirs(),
+                vec![PathBuf::from("/run/foo"), PathBuf::from("/run/bar")]
+            );
+            assert_eq!(state_dir(), None);
+            assert_eq!(cache_dir(), Some(PathBuf::from("/var/cache/foo")));
+        }
+
+        assert_eq!(runtime_dir(), Some(PathBuf::from("/run/original")));
+        assert_eq!(state_dir(), Some(PathBuf::from("/var/lib/original")));
+        assert_eq!(env::var_os("CACHE_DIRECTORY"), None);
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_new_for_current_pid() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+
+        env::remove_var("LISTEN_PID");
+        let dirs = SystemdDirs::new_for_current_pid();
+        assert_eq!(dirs.runtime_dir(), Some(Path::new("/run/foo")));
+
+        env::set_var("LISTEN_PID", process::id().to_string());
+        let dirs = SystemdDirs::new_for_current_pid();
+        assert_eq!(dirs.runtime_dir(), Some(Path::new("/run/foo")));
+
+        env::set_var("LISTEN_PID", (process::id() + 1).to_string());
+        let dirs = SystemdDirs::new_for_current_pid();
+        assert!(dirs.is_empty());
+
+        env::set_var("LISTEN_PID", "not a pid");
+        let dirs = SystemdDirs::new_for_current_pid();
+        assert!(dirs.is_empty());
+
+        env::remove_var("LISTEN_PID");
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(feature = "runtime")]
+    #[cfg(unix)]
+    #[test]
+    fn test_runtime_dirs_symlinks() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let real = temp.path().join("real");
+        let symlink = temp.path().join("symlink");
+        fs::create_dir(&real).unwrap();
+        std::os::unix::fs::symlink(&real, &symlink).unwrap();
+
+        env::set_var(
+            "RUNTIME_DIRECTORY",
+            format!("{}:{}", real.display(), symlink.display()),
+        );
+        let dirs = SystemdDirs::new();
+        assert_eq!(
+            dirs.runtime_dirs_symlinks().unwrap(),
+            vec![symlink.as_path()]
+        );
+
+        env::set_var("RUNTIME_DIRECTORY", temp.path().join("missing"));
+        let dirs = SystemdDirs::new();
+        assert_eq!(
+            dirs.runtime_dirs_symlinks().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_get() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/foo");
+        env::set_var("LOGS_DIRECTORY", "/var/log/foo");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let dirs = SystemdDirs::new();
+        for kind in DirKind::ALL {
+            env::remove_var(kind.env_key());
+        }
+
+        let all = DirKind::ALL
+            .into_iter()
+            .map(|kind| dirs.get(kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            all,
+            vec![
+                &[PathBuf::from("/run/foo"), PathBuf::from("/run/bar")][..],
+                &[PathBuf::from("/var/lib/foo")],
+                &[PathBuf::from("/var/cache/foo")],
+                &[PathBuf::from("/var/log/foo")],
+                &[PathBuf::from("/etc/foo")],
+            ]
+        );
+        assert!(SystemdDirs::new().get(DirKind::Runtime).is_empty());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_new_unquoted() {
+        let _env = lock_env();
+        env::set_var(
+            "RUNTIME_DIRECTORY",
+            r#"'/run/a':"/run/b":/run/c:/run/it's:'/run/d":"/run/e:''"#,
+        );
+        let dirs = SystemdDirs::new_unquoted();
+        assert_eq!(dirs.raw_segment_info(DirKind::Runtime), Some((7, 6)));
+        assert_eq!(
+            dirs.runtime_dirs(),
+            vec![
+                Path::new("/run/a"),
+                Path::new("/run/b"),
+                Path::new("/run/c"),
+                Path::new("/run/it's"),
+                Path::new(r#"'/run/d""#),
+                Path::new(r#""/run/e"#),
+            ]
+        );
+
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.runtime_dir(), Some(Path::new("'/run/a'")));
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_socket_path() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        let dirs = SystemdDirs::new();
+        assert_eq!(
+            dirs.runtime_socket_path("control.sock"),
+            PathBuf::from("/run/foo/control.sock")
+        );
+
+        env::remove_var("RUNTIME_DIRECTORY");
+        let dirs = SystemdDirs::new();
+        assert_eq!(
+            dirs.runtime_socket_path("control.sock"),
+            env::temp_dir().join("control.sock")
+        );
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_create_all() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let runtime = temp.path().join("run/foo");
+        let state = temp.path().join("lib/foo");
+        env::set_var("RUNTIME_DIRECTORY", &runtime);
+        env::set_var("STATE_DIRECTORY", &state);
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        dirs.create_all().unwrap();
+        assert!(runtime.is_dir());
+        assert!(state.is_dir());
+        SystemdDirs::new().create_all().unwrap();
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_create_all_error() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let file = temp.path().join("file");
+        fs::write(&file, "").unwrap();
+        env::set_var("CACHE_DIRECTORY", file.join("foo"));
+        let dirs = SystemdDirs::new();
+        env::remove_var("CACHE_DIRECTORY");
+        assert!(dirs.create_all().is_err());
+    }
+
+    #[cfg(feature = "cache")]
+    #[cfg(unix)]
+    #[test]
+    fn test_create_all_permission_denied() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let Some(read_only) = read_only_dir(temp.path(), "read-only") else {
+            return;
+        };
+
+        env::set_var("CACHE_DIRECTORY", read_only.join("foo"));
+        let dirs = SystemdDirs::new();
+        env::remove_var("CACHE_DIRECTORY");
+        assert_eq!(
+            dirs.create_all().unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_all_dirs() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/foo");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let all = all_dirs();
+        assert_eq!(
+            all,
+            AllDirs {
+                runtime_dirs: runtime_dirs(),
+                state_dirs: state_dirs(),
+                cache_dirs: cache_dirs(),
+                logs_dirs: logs_dirs(),
+                config_dirs: config_dirs(),
+            }
+        );
+        assert_eq!(all.runtime_dirs.len(), 2);
+        assert!(all.state_dirs.is_empty());
+
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+        env::remove_var("CONFIGURATION_DIRECTORY");
+        assert_eq!(all_dirs(), AllDirs::default());
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_is_multiple() {
+        let _env = lock_env();
+        let is_multiple = |value: Option<&str>| {
+            match value {
+                Some(value) => env::set_var("STATE_DIRECTORY", value),
+                None => env::remove_var("STATE_DIRECTORY"),
+            }
+
+            SystemdDirs::new().state_is_multiple()
+        };
+
+        assert!(!is_multiple(None));
+        assert!(!is_multiple(Some("/var/lib/foo")));
+        assert!(is_multiple(Some("/var/lib/foo:/var/lib/bar")));
+        env::remove_var("STATE_DIRECTORY");
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_validate_limits() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/a/b/c/d");
+        env::set_var("STATE_DIRECTORY", "/var/lib/a-very-long-name");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(dirs.validate_limits(32, 5), Ok(()));
+        assert_eq!(
+            dirs.validate_limits(32, 4),
+            Err(vec![(DirKind::Runtime, PathBuf::from("/run/a/b/c/d"))])
+        );
+        assert_eq!(
+            dirs.validate_limits(16, 5),
+            Err(vec![(
+                DirKind::State,
+                PathBuf::from("/var/lib/a-very-long-name")
+            )])
+        );
+        assert_eq!(dirs.validate_limits(8, 5).unwrap_err().len(), 2);
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_dir_url() {
+        let _env = lock_env();
+        env::set_var(
+            "CACHE_DIRECTORY",
+            "/var/cache/my app/caf\u{e9}:/var/cache/bar",
+        );
+        assert_eq!(
+            cache_dir_url(),
+            Some(String::from("file:///var/cache/my%20app/caf%C3%A9"))
+        );
+
+        env::set_var("CACHE_DIRECTORY", "/var/cache/a%b#c?d");
+        assert_eq!(
+            cache_dir_url(),
+            Some(String::from("file:///var/cache/a%25b%23c%3Fd"))
+        );
+
+        env::set_var("CACHE_DIRECTORY", "relative/cache");
+        assert_eq!(cache_dir_url(), None);
+        env::remove_var("CACHE_DIRECTORY");
+        assert_eq!(cache_dir_url(), None);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "cache", feature = "config"))]
+    #[test]
+    fn test_overrides_xdg() {
+        let _env = lock_env();
+        let xdg = [
+            "XDG_RUNTIME_DIR",
+            "XDG_STATE_HOME",
+            "XDG_CACHE_HOME",
+            "XDG_CONFIG_HOME",
+            "HOME",
+        ];
+        let previous = xdg.map(|key| (key, env::var_os(key)));
+        env::set_var("HOME", "/home/user");
+        env::set_var("XDG_RUNTIME_DIR", "/run/user/1000");
+        env::set_var("XDG_CACHE_HOME", "/home/user/.my-cache");
+        env::remove_var("XDG_STATE_HOME");
+        env::remove_var("XDG_CONFIG_HOME");
+
+        env::set_var("RUNTIME_DIRECTORY", "/run/user/1000/app");
+        env::set_var("STATE_DIRECTORY", "/home/user/.local/state/app");
+        env::set_var("CACHE_DIRECTORY", "/home/user/.cache/app");
+        env::set_var("LOGS_DIRECTORY", "/home/user/.local/state/log/app");
+        env::set_var("CONFIGURATION_DIRECTORY", "/home/user/.config/app:/etc/app");
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.overrides_xdg(), vec![DirKind::Cache, DirKind::Config]);
+
+        env::remove_var("XDG_RUNTIME_DIR");
+        assert_eq!(
+            dirs.overrides_xdg(),
+            vec![DirKind::Runtime, DirKind::Cache, DirKind::Config]
+        );
+
+        for kind in DirKind::ALL {
+            env::remove_var(kind.env_key());
+        }
+
+        assert!(SystemdDirs::new().overrides_xdg().is_empty());
+        for (key, value) in previous {
+            match value {
+                Some(value) => env::set_var(key, value),
+                None => env::remove_var(key),
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_environ() {
+        let vars = parse_environ(
+            b"RUNTIME_DIRECTORY=/run/foo:/run/bar\0EMPTY=\0NO_SEPARATOR\0BAD=\xff\0A=b=c\0",
+        );
+        assert_eq!(
+            vars,
+            HashMap::from([
+                (
+                    String::from("RUNTIME_DIRECTORY"),
+                    String::from("/run/foo:/run/bar")
+                ),
+                (String::from("EMPTY"), String::new()),
+                (String::from("BAD"), String::new()),
+                (String::from("A"), String::from("b=c")),
+            ])
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_from_pid() {
+        let environ = fs::read("/proc/self/environ").unwrap();
+        let vars = parse_environ(&environ);
+        let expected = SystemdDirs::from_lookup(|key| vars.get(key).cloned());
+        let dirs = SystemdDirs::from_pid(process::id()).unwrap();
+        assert!(dirs.changed_since(&expected).is_empty());
+
+        let err = SystemdDirs::from_pid(u32::MAX).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(all(feature = "tokio", target_os = "linux"))]
+    #[tokio::test]
+    async fn test_from_pid_async() {
+        let environ = tokio::fs::read("/proc/self/environ").await.unwrap();
+        let vars = parse_environ(&environ);
+        let expected = SystemdDirs::from_lookup(|key| vars.get(key).cloned());
+        let dirs = SystemdDirs::from_pid_async(process::id()).await.unwrap();
+        assert!(dirs.changed_since(&expected).is_empty());
+
+        let err = SystemdDirs::from_pid_async(u32::MAX).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "cache", feature = "config"))]
+    #[test]
+    fn test_present_vars() {
+        let _env = lock_env();
+        env::set_var("CACHE_DIRECTORY", "");
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+        let dirs = SystemdDirs::new();
+        env::remove_var("CACHE_DIRECTORY");
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        assert!(dirs.cache_dirs().is_empty());
+        assert_eq!(dirs.present_vars(), vec![DirKind::Runtime, DirKind::Cache]);
+        assert!(SystemdDirs::new().present_vars().is_empty());
+    }
+
+    #[test]
+    fn test_kinds_by_priority() {
+        assert_eq!(
+            SystemdDirs::kinds_by_priority(),
+            [
+                DirKind::Config,
+                DirKind::State,
+                DirKind::Cache,
+                DirKind::Logs,
+                DirKind::Runtime
+            ]
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_dirs_filter() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/srv/run/bar:/run/baz");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        assert_eq!(
+            dirs.runtime_dirs_filter(|p| p.starts_with("/run")),
+            vec![Path::new("/run/foo"), Path::new("/run/baz")]
+        );
+        assert!(dirs.runtime_dirs_filter(|_| false).is_empty());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "logs"))]
+    #[test]
+    fn test_to_debug_lines() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/a:/run/b");
+        env::set_var("LOGS_DIRECTORY", "/var/log/a");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("LOGS_DIRECTORY");
+
+        assert_eq!(
+            dirs.to_debug_lines(),
+            vec![
+                "version=1",
+                "runtime=/run/a:/run/b",
+                "state=",
+                "cache=",
+                "logs=/var/log/a",
+                "config=",
+            ]
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_dir_mode() {
+        let _env = lock_env();
+        let mode = |value: &str| {
+            env::set_var("RUNTIME_DIRECTORY_MODE", value);
+            runtime_dir_mode()
+        };
+
+        assert_eq!(mode("0700"), Some(0o700));
+        assert_eq!(mode("0755"), Some(0o755));
+        assert_eq!(mode("755"), Some(0o755));
+        assert_eq!(mode("garbage"), None);
+        assert_eq!(mode("0789"), None);
+        assert_eq!(mode("+755"), None);
+        assert_eq!(mode("17777"), None);
+        assert_eq!(mode(""), None);
+        env::remove_var("RUNTIME_DIRECTORY_MODE");
+        assert_eq!(runtime_dir_mode(), None);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_wait_for_runtime_dir() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let runtime = temp.path().join("runtime");
+        env::set_var("RUNTIME_DIRECTORY", &runtime);
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        let err = dirs
+            .wait_for_runtime_dir(Duration::from_millis(20))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let creator = {
+            let runtime = runtime.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::create_dir(runtime).unwrap();
+            })
+        };
+
+        assert_eq!(
+            dirs.wait_for_runtime_dir(Duration::from_secs(5)).unwrap(),
+            Some(runtime.as_path())
+        );
+        creator.join().unwrap();
+
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.wait_for_runtime_dir(Duration::ZERO).unwrap(), None);
+    }
+
+    #[cfg(all(feature = "state", feature = "cache", feature = "logs"))]
+    #[test]
+    fn test_relative_path() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", "/var/lib/app");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/app");
+        env::set_var("LOGS_DIRECTORY", "/var/lib/app/log");
+        env::set_var("CONFIGURATION_DIRECTORY", "relative/app");
+        let dirs = SystemdDirs::new();
+        env::remove_var("STATE_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+        env::remove_var("LOGS_DIRECTORY");
+        env::remove_var("CONFIGURATION_DIRECTORY");
+
+        let relative = |from, to| dirs.relative_path(from, to);
+        assert_eq!(
+            relative(DirKind::State, DirKind::Cache),
+            Some(PathBuf::from("../../cache/app"))
+        );
+        assert_eq!(
+            relative(DirKind::State, DirKind::Logs),
+            Some(PathBuf::from("log"))
+        );
+        assert_eq!(
+            relative(DirKind::Logs, DirKind::State),
+            Some(PathBuf::from(".."))
+        );
+        assert_eq!(
+            relative(DirKind::State, DirKind::State),
+            Some(PathBuf::from("."))
+        );
+        assert_eq!(relative(DirKind::State, DirKind::Config), None);
+        assert_eq!(relative(DirKind::Runtime, DirKind::State), None);
+        assert_eq!(relative(DirKind::State, DirKind::Runtime), None);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_new_with_separators() {
+        let _env = lock_env();
+        let runtime_dirs = |value: &str, separators: &[char]| {
+            env::set_var("RUNTIME_DIRECTORY", value);
+            SystemdDirs::new_with_separators(separators)
+                .runtime_dirs
+                .clone()
+        };
+
+        let expected = vec![PathBuf::from("/run/a"), PathBuf::from("/run/b")];
+        assert_eq!(runtime_dirs("/run/a:/run/b", &[':']), expected);
+        assert_eq!(runtime_dirs("/run/a;/run/b", &[';']), expected);
+        assert_eq!(
+            runtime_dirs("/run/a;/run/b:/run/c", &[':', ';']),
+            vec![
+                PathBuf::from("/run/a"),
+                PathBuf::from("/run/b"),
+                PathBuf::from("/run/c")
+            ]
+        );
+        assert_eq!(
+            runtime_dirs("/run/a;/run/b", &[':']),
+            vec![PathBuf::from("/run/a;/run/b")]
+        );
+        assert_eq!(
+            runtime_dirs("/run/a:/run/b", &[]),
+            vec![PathBuf::from("/run/a:/run/b")]
+        );
+        env::remove_var("RUNTIME_DIRECTORY");
+    }
+
+    #[cfg(feature = "runtime")]
+    #[cfg(unix)]
+    #[test]
+    fn test_runtime_dir_owned_by_me() {
+        let _env = lock_env();
+        #[cfg(target_os = "linux")]
+        {
+            let status = fs::read_to_string("/proc/self/status").unwrap();
+            let uids = status.lines().find_map(|line| line.strip_prefix("Uid:"));
+            let euid = uids.and_then(|uids| uids.split_whitespace().nth(1));
+            assert_eq!(
+                euid,
+                Some(rustix::process::geteuid().as_raw().to_string().as_str())
+            );
+        }
+
+        let temp = TempDir::new();
+        env::set_var("RUNTIME_DIRECTORY", temp.path());
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.runtime_dir_owned_by_me().unwrap(), Some(true));
+
+        env::set_var("RUNTIME_DIRECTORY", temp.path().join("missing"));
+        let dirs = SystemdDirs::new();
+        assert!(dirs.runtime_dir_owned_by_me().is_err());
+
+        env::remove_var("RUNTIME_DIRECTORY");
+        let dirs = SystemdDirs::new();
+        assert_eq!(dirs.runtime_dir_owned_by_me().unwrap(), None);
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_into_iter() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+        env::set_var("STATE_DIRECTORY", "/var/lib/foo:/var/lib/bar");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/foo");
+        env::set_var("LOGS_DIRECTORY", "/var/log/foo");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/foo");
+        let dirs = SystemdDirs::new();
+        for kind in DirKind::ALL {
+            env::remove_var(kind.env_key());
+        }
+
+        let paths: Vec<PathBuf> = dirs.into_iter().collect();
+        assert_eq!(
+            paths,
+            [
+                "/etc/foo",
+                "/var/lib/foo",
+                "/var/lib/bar",
+                "/var/cache/foo",
+                "/var/log/foo",
+                "/run/foo",
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_first_writable() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let writable = temp.path().join("writable");
+        fs::create_dir(&writable).unwrap();
+
+        // Root can write to read-only directories, so only the writable directory is checked then.
+        let mut paths: Vec<PathBuf> = read_only_dir(temp.path(), "read-only")
+            .into_iter()
+            .collect();
+        paths.push(writable.clone());
+        env::set_var("STATE_DIRECTORY", join_colons(&paths));
+        let dirs = SystemdDirs::new();
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(
+            dirs.state_dir_first_writable().unwrap(),
+            Some(writable.as_path())
+        );
+        assert!(fs::read_dir(&writable).unwrap().next().is_none());
+        assert_eq!(dirs.runtime_dir_first_writable().unwrap(), None);
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_fingerprint() {
+        let _env = lock_env();
+        let lookup = |value: &'static str| {
+            move |key: &str| (key == "STATE_DIRECTORY").then(|| value.to_string())
+        };
+
+        let a = SystemdDirs::from_lookup(lookup("/var/lib/foo:/var/lib/bar"));
+        let b = SystemdDirs::from_lookup(lookup("/var/lib/foo:/var/lib/bar"));
+        let c = SystemdDirs::from_lookup(lookup("/var/lib/foo/var/lib/bar"));
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), c.fingerprint());
+        assert_ne!(
+            a.fingerprint(),
+            SystemdDirs::from_lookup(|_| None).fingerprint()
+        );
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state", feature = "cache"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_str() {
+        use std::os::unix::ffi::OsStringExt;
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|_| None)
+            .with_state_dirs(vec![PathBuf::from("/var/lib/foo")])
+            .with_cache_dirs(vec![PathBuf::from(OsString::from_vec(
+                b"/var/cache/\xff".to_vec(),
+            ))]);
+
+        assert_eq!(dirs.state_dir_str(), Some("/var/lib/foo"));
+        assert!(dirs.cache_dir().is_some());
+        assert_eq!(dirs.cache_dir_str(), None);
+        assert_eq!(dirs.runtime_dir_str(), None);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state", feature = "cache"))]
+    #[test]
+    fn test_with_prefix() {
+        let _env = lock_env();
+        env::set_var("FOO_RUNTIME_DIRECTORY", "/run/foo");
+        env::set_var("RUNTIME_DIRECTORY", "/run/bare");
+        env::set_var("STATE_DIRECTORY", "/var/lib/bare");
+        let dirs = SystemdDirs::with_prefix("FOO_");
+        env::remove_var("FOO_RUNTIME_DIRECTORY");
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(dirs.runtime_dir(), Some(Path::new("/run/foo")));
+        assert_eq!(dirs.state_dir(), Some(Path::new("/var/lib/bare")));
+        assert_eq!(dirs.cache_dir(), None);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.conf", "foo.conf"));
+        assert!(glob_match("*.conf", ".conf"));
+        assert!(glob_match("f?o*", "foo.conf"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("*.conf", "foo.conf.bak"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("foo", "food"));
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_dir_glob() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        for name in ["b.conf", "a.conf", "c.txt"] {
+            fs::write(temp.path().join(name), "").unwrap();
+        }
+
+        fs::create_dir(temp.path().join("d.conf.d")).unwrap();
+        env::set_var("CONFIGURATION_DIRECTORY", temp.path());
+        let dirs = SystemdDirs::new();
+        env::remove_var("CONFIGURATION_DIRECTORY");
+
+        assert_eq!(
+            dirs.config_dir_glob("*.conf").unwrap(),
+            vec![temp.path().join("a.conf"), temp.path().join("b.conf")]
+        );
+        assert!(SystemdDirs::new()
+            .config_dir_glob("*.conf")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_inferred_name() {
+        let _env = lock_env();
+        let dirs = |runtime: &'static str, state: &'static str| {
+            SystemdDirs::from_lookup(move |key| match key {
+                "RUNTIME_DIRECTORY" => Some(runtime.to_string()),
+                "STATE_DIRECTORY" => Some(state.to_string()),
+                _ => None,
+            })
+        };
+
+        assert_eq!(
+            dirs("/run/myapp", "/var/lib/myapp").inferred_name(),
+            Some(String::from("myapp"))
+        );
+        assert_eq!(dirs("/run/myapp", "/var/lib/other").inferred_name(), None);
+        assert_eq!(dirs("/", "/").inferred_name(), None);
+        assert_eq!(SystemdDirs::from_lookup(|_| None).inferred_name(), None);
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_new_large_environment() {
+        let _env = lock_env();
+        let unrelated: Vec<String> = (0..1000)
+            .map(|i| format!("SYSTEMD_DIRECTORIES_TEST_UNRELATED_{i}"))
+            .collect();
+        for key in &unrelated {
+            env::set_var(key, "/unrelated");
+        }
+
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("CACHE_DIRECTORY", ":");
+        let dirs = SystemdDirs::new();
+        let expected = SystemdDirs::from_lookup(env_lookup);
+        for key in &unrelated {
+            env::remove_var(key);
+        }
+
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+
+        assert!(dirs.changed_since(&expected).is_empty());
+        assert_eq!(dirs.present_vars(), expected.present_vars());
+        assert_eq!(dirs.check_empty(), vec![DirKind::Cache]);
+    }
+
+    #[cfg(feature = "state")]
+    #[cfg(unix)]
+    #[test]
+    fn test_new_not_unicode() {
+        use std::os::unix::ffi::OsStrExt;
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY", OsStr::from_bytes(b"/var/lib/\xff"));
+        let dirs = SystemdDirs::new();
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(dirs.state_dir(), None);
+        assert_eq!(dirs.check_empty(), vec![DirKind::State]);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_existence_report() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let missing = temp.path().join("missing");
+        env::set_var("RUNTIME_DIRECTORY", temp.path());
+        env::set_var("STATE_DIRECTORY", &missing);
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(
+            dirs.existence_report(),
+            vec![
+                (DirKind::Runtime, temp.path().to_path_buf(), true),
+                (DirKind::State, missing, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_systemd_dir_conversions() {
+        let dir = SystemdDir::new(DirKind::Runtime, "/run/foo");
+        assert!(dir == Path::new("/run/foo"));
+        assert!(dir == *Path::new("/run/foo"));
+        assert!(dir != Path::new("/run/bar"));
+        assert_eq!(AsRef::<OsStr>::as_ref(&dir), OsStr::new("/run/foo"));
+
+        let mut sizes: HashMap<PathBuf, u32> = HashMap::new();
+        sizes.insert(PathBuf::from("/run/foo"), 42);
+        assert_eq!(sizes.get(Borrow::<Path>::borrow(&dir)), Some(&42));
+
+        assert_eq!(dir, SystemdDir::new(DirKind::Cache, "/run/foo"));
+        assert_ne!(dir, SystemdDir::new(DirKind::Runtime, "/run/bar"));
+
+        let mut by_dir: HashMap<SystemdDir, u32> = HashMap::new();
+        by_dir.insert(dir, 7);
+        assert_eq!(by_dir.get(Path::new("/run/foo")), Some(&7));
+        by_dir.insert(SystemdDir::new(DirKind::Cache, "/run/foo"), 8);
+        assert_eq!(by_dir.len(), 1);
+        assert_eq!(by_dir.get(Path::new("/run/foo")), Some(&8));
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state", feature = "cache"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_group_by_device() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let runtime = temp.path().join("runtime");
+        let cache = temp.path().join("cache");
+        fs::create_dir(&runtime).unwrap();
+        fs::create_dir(&cache).unwrap();
+        env::set_var("RUNTIME_DIRECTORY", &runtime);
+        env::set_var("CACHE_DIRECTORY", &cache);
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+
+        let groups = dirs.group_by_device().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups.into_values().next().unwrap(),
+            vec![runtime.as_path(), cache.as_path()]
+        );
+
+        let dirs = dirs.with_state_dirs(vec![temp.path().join("missing")]);
+        assert!(dirs.group_by_device().is_err());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_resolve_specifiers() {
+        let _env = lock_env();
+        let previous = env::var_os("XDG_RUNTIME_DIR");
+        env::remove_var("XDG_RUNTIME_DIR");
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/myapp")),
+            "STATE_DIRECTORY" => Some(String::from("/var/lib/myapp")),
+            _ => None,
+        });
+
+        assert_eq!(
+            resolve_specifiers("%t/sock", &dirs),
+            Some(PathBuf::from("/run/sock"))
+        );
+        assert_eq!(
+            resolve_specifiers("%S/db", &dirs),
+            Some(PathBuf::from("/var/lib/db"))
+        );
+        assert_eq!(
+            resolve_specifiers("/tmp/100%%", &dirs),
+            Some(PathBuf::from("/tmp/100%"))
+        );
+        assert_eq!(resolve_specifiers("%C/foo", &dirs), None);
+        assert_eq!(resolve_specifiers("%h/foo", &dirs), None);
+        assert_eq!(resolve_specifiers("%t%", &dirs), None);
+
+        let nested = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/myapp/sub")),
+            "STATE_DIRECTORY" => Some(String::from("/var/lib/myapp/a/b")),
+            _ => None,
+        });
+        assert_eq!(
+            resolve_specifiers("%t/sock", &nested),
+            Some(PathBuf::from("/run/sock"))
+        );
+        assert_eq!(
+            resolve_specifiers("%S/db", &nested),
+            Some(PathBuf::from("/var/lib/db"))
+        );
+
+        env::set_var("XDG_RUNTIME_DIR", "/run/user/1000");
+        let user = SystemdDirs::from_lookup(|key| {
+            (key == "RUNTIME_DIRECTORY").then(|| String::from("/run/user/1000/myapp/sub"))
+        });
+        let resolved = resolve_specifiers("%t/sock", &user);
+        match previous {
+            Some(value) => env::set_var("XDG_RUNTIME_DIR", value),
+            None => env::remove_var("XDG_RUNTIME_DIR"),
+        }
+        assert_eq!(resolved, Some(PathBuf::from("/run/user/1000/sock")));
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_dir_for_purpose() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| {
+            Some(String::from(match key {
+                "RUNTIME_DIRECTORY" => "/run/foo",
+                "STATE_DIRECTORY" => "/var/lib/foo",
+                "CACHE_DIRECTORY" => "/var/cache/foo",
+                "LOGS_DIRECTORY" => "/var/log/foo",
+                "CONFIGURATION_DIRECTORY" => "/etc/foo",
+                _ => return None,
+            }))
+        });
+
+        let expected = [
+            (Purpose::TemporaryFiles, DirKind::Runtime, "/run/foo"),
+            (Purpose::PersistentData, DirKind::State, "/var/lib/foo"),
+            (Purpose::RegenerableData, DirKind::Cache, "/var/cache/foo"),
+            (Purpose::Logs, DirKind::Logs, "/var/log/foo"),
+            (Purpose::Configuration, DirKind::Config, "/etc/foo"),
+        ];
+        for (purpose, kind, path) in expected {
+            assert_eq!(purpose.kind(), kind);
+            assert_eq!(dirs.dir_for_purpose(purpose), Some(Path::new(path)));
+        }
+
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert_eq!(dirs.dir_for_purpose(Purpose::Logs), None);
     }
 
-    /// Returns the first state directory as defined by `StateDirectory` in the unit file.
-    ///
-    /// If the environment variable `STATE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
-    /// If it was set, it returns the first path in the colon-separated list.
-    /// To get all paths, use [`Self::state_dirs`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let state_dir = dirs.state_dir();
-    /// ```
-    pub fn state_dir(&self) -> Option<&Path> {
-        self.as_paths(&self.state_dirs).next()
+    #[cfg(all(feature = "state", feature = "logs"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_distinct_device_count() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let state = temp.path().join("state");
+        let logs = temp.path().join("logs");
+        fs::create_dir(&state).unwrap();
+        fs::create_dir(&logs).unwrap();
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert_eq!(dirs.distinct_device_count().unwrap(), 0);
+
+        let dirs = dirs.with_state_dirs(vec![state]).with_logs_dirs(vec![logs]);
+        assert_eq!(dirs.distinct_device_count().unwrap(), 1);
     }
 
-    /// Returns all cache directories as defined by `CacheDirectory` in the unit file.
-    ///
-    /// If the environment variable `CACHE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
-    /// If it was set, it returns all paths in the colon-separated list.
-    /// To get just the first path, use [`Self::cache_dir`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let cache_dirs = dirs.cache_dirs();
-    /// ```
-    pub fn cache_dirs(&self) -> Vec<&Path> {
-        self.as_paths(&self.cache_dirs).collect()
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_to_shell_exports() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/a:/run/b")),
+            "STATE_DIRECTORY" => Some(String::from("/var/lib/it's")),
+            _ => None,
+        });
+
+        assert_eq!(
+            dirs.to_shell_exports(),
+            "export RUNTIME_DIRECTORY='/run/a:/run/b'\nexport STATE_DIRECTORY='/var/lib/it'\\''s'\n"
+        );
+        assert_eq!(SystemdDirs::from_lookup(|_| None).to_shell_exports(), "");
     }
 
-    /// Returns the first cache directory as defined by `CacheDirectory` in the unit file.
-    ///
-    /// If the environment variable `CACHE_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
-    /// If it was set, it returns the first path in the colon-separated list.
-    /// To get all paths, use [`Self::cache_dirs`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let cache_dir = dirs.cache_dir();
-    /// ```
-    pub fn cache_dir(&self) -> Option<&Path> {
-        self.as_paths(&self.cache_dirs).next()
+    #[test]
+    fn test_dir_kind_bases() {
+        let expected = [
+            (DirKind::Runtime, "/run", "XDG_RUNTIME_DIR"),
+            (DirKind::State, "/var/lib", "XDG_STATE_HOME"),
+            (DirKind::Cache, "/var/cache", "XDG_CACHE_HOME"),
+            (DirKind::Logs, "/var/log", "XDG_STATE_HOME"),
+            (DirKind::Config, "/etc", "XDG_CONFIG_HOME"),
+        ];
+        for (kind, system_base, user_base_env) in expected {
+            assert_eq!(kind.system_base(), system_base);
+            assert_eq!(kind.user_base_env(), user_base_env);
+        }
+
+        const RUNTIME_BASE: &str = DirKind::Runtime.system_base();
+        assert_eq!(RUNTIME_BASE, "/run");
     }
 
-    /// Returns all logs directories as defined by `LogsDirectory` in the unit file.
-    ///
-    /// If the environment variable `LOGS_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
-    /// If it was set, it returns all paths in the colon-separated list.
-    /// To get just the first path, use [`Self::logs_dir`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let logs_dirs = dirs.logs_dirs();
-    /// ```
-    pub fn logs_dirs(&self) -> Vec<&Path> {
-        self.as_paths(&self.logs_dirs).collect()
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_insecure_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let [private, world, sticky] =
+            ["private", "world", "sticky"].map(|name| temp.path().join(name));
+        for (dir, mode) in [(&private, 0o755), (&world, 0o777), (&sticky, 0o1777)] {
+            fs::create_dir(dir).unwrap();
+            fs::set_permissions(dir, fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let dirs = SystemdDirs::from_lookup(|_| None)
+            .with_state_dirs(vec![private, world.clone()])
+            .with_cache_dirs(vec![sticky]);
+        assert_eq!(dirs.insecure_dirs().unwrap(), vec![(DirKind::State, world)]);
     }
 
-    /// Returns the first logs directory as defined by `LogsDirectory` in the unit file.
-    ///
-    /// If the environment variable `LOGS_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
-    /// If it was set, it returns the first path in the colon-separated list.
-    /// To get all paths, use [`Self::logs_dirs`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let logs_dir = dirs.logs_dir();
-    /// ```
-    pub fn logs_dir(&self) -> Option<&Path> {
-        self.as_paths(&self.logs_dirs).next()
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_raw_segment_info() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "");
+        env::set_var("STATE_DIRECTORY", "a::b");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(dirs.raw_segment_info(DirKind::Runtime), Some((1, 0)));
+        assert_eq!(dirs.raw_segment_info(DirKind::State), Some((3, 2)));
+        assert_eq!(dirs.raw_segment_info(DirKind::Cache), None);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_dirs_with_suffix() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        assert_eq!(
+            dirs.runtime_dirs_with_suffix("sockets/v1"),
+            vec![
+                PathBuf::from("/run/foo/sockets/v1"),
+                PathBuf::from("/run/bar/sockets/v1"),
+            ]
+        );
+        assert!(dirs.state_dirs_with_suffix("data").is_empty());
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_eq_ignoring_order() {
+        let _env = lock_env();
+        let lookup = |value: &'static str| {
+            move |key: &str| (key == "CACHE_DIRECTORY").then(|| value.to_string())
+        };
+
+        let a = SystemdDirs::from_lookup(lookup("/var/cache/a:/var/cache/b"));
+        let b = SystemdDirs::from_lookup(lookup("/var/cache/b:/var/cache/a"));
+        let c = SystemdDirs::from_lookup(lookup("/var/cache/a:/var/cache/c"));
+        assert!(a.eq_ignoring_order(&b));
+        assert_ne!(a, b);
+        assert!(!a.eq_ignoring_order(&c));
+        assert_eq!(a, a.clone());
+
+        let trailing = SystemdDirs::from_lookup(lookup("/var/cache/a:/var/cache/b:"));
+        assert_ne!(
+            a.raw_segment_info(DirKind::Cache),
+            trailing.raw_segment_info(DirKind::Cache)
+        );
+        assert_eq!(a, trailing);
+        assert_eq!(
+            SystemdDirs::from_lookup(|_| None).with_cache_dirs(vec![
+                PathBuf::from("/var/cache/a"),
+                PathBuf::from("/var/cache/b")
+            ]),
+            a
+        );
+    }
+
+    #[cfg(all(
+        feature = "serde",
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_from_json() {
+        let _env = lock_env();
+        let json = r#"
+            {
+                "runtime": ["/run/foo", "/run/bar"],
+                "state": ["/var/lib/café", ""],
+                "logs": [],
+                "config": ["/etc/\"quoted\"\\dir"]
+            }
+        "#;
+        let dirs = SystemdDirs::from_json(json.as_bytes()).unwrap();
+        assert_eq!(
+            dirs.runtime_dirs(),
+            vec![Path::new("/run/foo"), Path::new("/run/bar")]
+        );
+        assert_eq!(dirs.state_dirs(), vec![Path::new("/var/lib/café")]);
+        assert!(dirs.cache_dirs().is_empty());
+        assert!(dirs.logs_dirs().is_empty());
+        assert_eq!(dirs.config_dirs(), vec![Path::new("/etc/\"quoted\"\\dir")]);
+        assert_eq!(
+            dirs.present_vars(),
+            vec![
+                DirKind::Runtime,
+                DirKind::State,
+                DirKind::Logs,
+                DirKind::Config
+            ]
+        );
+        assert_eq!(dirs.raw_segment_info(DirKind::State), Some((2, 1)));
+        assert_eq!(dirs.check_empty(), vec![DirKind::Logs]);
+
+        let emoji = SystemdDirs::from_json(r#"{"cache": ["/😀"]}"#.as_bytes()).unwrap();
+        assert_eq!(emoji.cache_dir(), Some(Path::new("/😀")));
+        assert!(SystemdDirs::from_json("{}".as_bytes()).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_from_json_invalid() {
+        let invalid = |json: &str| match SystemdDirs::from_json(json.as_bytes()) {
+            Err(JsonError::Invalid(e)) => e.to_string(),
+            other => panic!("expected invalid JSON, got {other:?}"),
+        };
+
+        assert!(invalid("").contains("EOF"));
+        assert!(invalid(r#"{"other": []}"#).contains("unknown field `other`"));
+        assert!(invalid(r#"{"cache": "/a"}"#).contains("invalid type"));
+        assert!(invalid(r#"{"cache": [1]}"#).contains("invalid type"));
+        assert!(invalid(r#"{"cache": [], "cache": []}"#).contains("duplicate field `cache`"));
+        assert!(invalid(r#"{"cache": ["/a]}"#).contains("EOF"));
+        assert!(invalid(r#"{"cache": ["\x"]}"#).contains("invalid escape"));
+        assert!(invalid(r#"{} {}"#).contains("trailing characters"));
+
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("failed"))
+            }
+        }
+
+        assert!(matches!(
+            SystemdDirs::from_json(Failing),
+            Err(JsonError::Io(_))
+        ));
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_disk_usage() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let nested = temp.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(temp.path().join("a"), [0; 10]).unwrap();
+        fs::write(nested.join("b"), [0; 32]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(temp.path().join("a"), nested.join("link")).unwrap();
+
+        let dirs =
+            SystemdDirs::from_lookup(|_| None).with_cache_dirs(vec![temp.path().to_path_buf()]);
+        assert_eq!(dirs.disk_usage(DirKind::Cache).unwrap(), 42);
+        assert_eq!(dirs.disk_usage(DirKind::State).unwrap(), 0);
+
+        let dirs = dirs.with_cache_dirs(vec![temp.path().join("missing")]);
+        assert!(dirs.disk_usage(DirKind::Cache).is_err());
+    }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[test]
+    fn test_new_validated() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let file = temp.path().join("file");
+        let missing = temp.path().join("missing");
+        fs::write(&file, "").unwrap();
+        env::set_var("RUNTIME_DIRECTORY", temp.path());
+        assert!(SystemdDirs::new_validated().is_ok());
+
+        env::set_var(
+            "STATE_DIRECTORY",
+            join_colons(&[missing.clone(), file.clone()]),
+        );
+        let error = SystemdDirs::new_validated().unwrap_err();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(error.missing(), [(DirKind::State, missing)]);
+        assert_eq!(error.not_directories(), [(DirKind::State, file)]);
+        assert!(error.to_string().contains("is not a directory"));
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_state_tempfile() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert!(dirs.state_tempfile().unwrap().is_none());
+
+        let dirs = dirs.with_state_dirs(vec![temp.path().to_path_buf()]);
+        let (_, first) = dirs.state_tempfile().unwrap().unwrap();
+        let (_, second) = dirs.state_tempfile().unwrap().unwrap();
+        assert_eq!(first.parent(), Some(temp.path()));
+        assert!(first.is_file());
+        assert_ne!(first, second);
+
+        let dirs = dirs.with_state_dirs(vec![temp.path().join("missing")]);
+        assert!(dirs.state_tempfile().is_err());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "cache", feature = "config"))]
+    #[test]
+    fn test_configured_kinds() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/foo")),
+            "CACHE_DIRECTORY" => Some(String::from("/var/cache/foo")),
+            "CONFIGURATION_DIRECTORY" => Some(String::from("/etc/foo")),
+            "STATE_DIRECTORY" => Some(String::from(":")),
+            _ => None,
+        });
+
+        assert_eq!(
+            dirs.configured_kinds().collect::<Vec<_>>(),
+            vec![DirKind::Config, DirKind::Cache, DirKind::Runtime]
+        );
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_runtime_or_state_dir() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert_eq!(dirs.runtime_or_state_dir(), None);
+
+        let dirs = dirs.with_state_dirs(vec![PathBuf::from("/var/lib/foo")]);
+        assert_eq!(dirs.runtime_or_state_dir(), Some(Path::new("/var/lib/foo")));
+
+        let dirs = dirs.with_runtime_dirs(vec![PathBuf::from("/run/foo")]);
+        assert_eq!(dirs.runtime_or_state_dir(), Some(Path::new("/run/foo")));
+    }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[test]
+    fn test_display_relative() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "STATE_DIRECTORY" => Some(String::from("/var/lib/foo")),
+            "CACHE_DIRECTORY" => Some(String::from("/var/cache/foo")),
+            _ => None,
+        });
+
+        assert_eq!(
+            dirs.display_relative("/var/lib/foo/db/foo.db"),
+            "$STATE_DIRECTORY/db/foo.db"
+        );
+        assert_eq!(dirs.display_relative("/var/cache/foo"), "$CACHE_DIRECTORY");
+        assert_eq!(dirs.display_relative("/var/lib/foobar"), "/var/lib/foobar");
+        assert_eq!(dirs.display_relative("/etc/foo"), "/etc/foo");
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn test_cached() {
+        let _env = lock_env();
+        let first = cached();
+        env::set_var("CACHE_DIRECTORY", "/var/cache/changed");
+        let second = cached();
+        env::remove_var("CACHE_DIRECTORY");
+
+        assert!(std::ptr::eq(first, second));
+        assert_eq!(first, second);
+        assert_ne!(second.cache_dir(), Some(Path::new("/var/cache/changed")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_systemd_version() {
+        let output = "\
+systemd 255 (255.4-1ubuntu8)
++PAM +AUDIT +SELINUX +APPARMOR +IMA +SMACK +SECCOMP +GCRYPT -GNUTLS +OPENSSL +ACL
+";
+        assert_eq!(parse_systemd_version(output), Some(255));
+        assert_eq!(
+            parse_systemd_version("systemd 254 (254.10-1.fc39)"),
+            Some(254)
+        );
+        assert_eq!(parse_systemd_version("systemd 256"), Some(256));
+        assert_eq!(parse_systemd_version("systemd (255)"), None);
+        assert_eq!(parse_systemd_version("udevadm 255"), None);
+        assert_eq!(parse_systemd_version(""), None);
+
+        // `systemd_version` depends on the host, so only check that it does not panic.
+        let _ = systemd_version();
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state", feature = "config"))]
+    #[test]
+    fn test_sorted_entries() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/b:/run/a")),
+            "STATE_DIRECTORY" => Some(String::from("/var/lib/foo:/run/a")),
+            "CONFIGURATION_DIRECTORY" => Some(String::from("/etc/foo")),
+            _ => None,
+        });
+
+        assert_eq!(
+            dirs.sorted_entries(),
+            vec![
+                (DirKind::Config, Path::new("/etc/foo")),
+                (DirKind::Runtime, Path::new("/run/a")),
+                (DirKind::State, Path::new("/run/a")),
+                (DirKind::Runtime, Path::new("/run/b")),
+                (DirKind::State, Path::new("/var/lib/foo")),
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_rebased() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/foo:relative")),
+            "STATE_DIRECTORY" => Some(String::from("/")),
+            _ => None,
+        });
+
+        let rebased = dirs.rebased("/mnt/chroot");
+        assert_eq!(
+            rebased.runtime_dirs(),
+            vec![Path::new("/mnt/chroot/run/foo"), Path::new("relative")]
+        );
+        assert_eq!(rebased.state_dir(), Some(Path::new("/mnt/chroot")));
+        assert_eq!(dirs.runtime_dir(), Some(Path::new("/run/foo")));
+    }
+
+    #[cfg(feature = "cache")]
+    #[cfg(all(unix, feature = "statvfs"))]
+    #[test]
+    fn test_available_space() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert_eq!(dirs.available_space(DirKind::Cache).unwrap(), None);
+
+        let dirs = dirs.with_cache_dirs(vec![temp.path().to_path_buf()]);
+        assert!(dirs.available_space(DirKind::Cache).unwrap().unwrap() > 0);
+
+        let dirs = dirs.with_cache_dirs(vec![temp.path().join("missing")]);
+        assert!(dirs.available_space(DirKind::Cache).is_err());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_try_get() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+        env::set_var("STATE_DIRECTORY", "");
+        let dirs = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert_eq!(
+            dirs.try_get(DirKind::Runtime),
+            Ok(&[PathBuf::from("/run/foo")][..])
+        );
+        assert_eq!(
+            dirs.try_get(DirKind::State),
+            Err(DirStatus::EmptyButPresent)
+        );
+        assert_eq!(dirs.try_get(DirKind::Cache), Err(DirStatus::Unset));
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_merge() {
+        let _env = lock_env();
+        let base = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/base")),
+            "CACHE_DIRECTORY" => Some(String::from("/var/cache/base")),
+            "LOGS_DIRECTORY" => Some(String::from("/var/log/base")),
+            _ => None,
+        });
+        let overlay = SystemdDirs::from_lookup(|key| match key {
+            "CACHE_DIRECTORY" => Some(String::from("/tmp/cache/a:/tmp/cache/b")),
+            "LOGS_DIRECTORY" => Some(String::from("")),
+            "CONFIGURATION_DIRECTORY" => Some(String::from("")),
+            _ => None,
+        });
+
+        let merged = base.merge(overlay);
+        assert_eq!(merged.runtime_dirs(), vec![Path::new("/run/base")]);
+        assert_eq!(
+            merged.cache_dirs(),
+            vec![Path::new("/tmp/cache/a"), Path::new("/tmp/cache/b")]
+        );
+        assert_eq!(merged.logs_dirs(), vec![Path::new("/var/log/base")]);
+        assert!(merged.state_dirs().is_empty());
+        assert!(merged.config_dirs().is_empty());
+        assert_eq!(merged.raw_segment_info(DirKind::Cache), Some((2, 2)));
+        assert_eq!(
+            merged.present_vars(),
+            vec![DirKind::Runtime, DirKind::Cache, DirKind::Logs]
+        );
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state", feature = "cache"))]
+    #[test]
+    fn test_new_with_fallbacks() {
+        let _env = lock_env();
+        let fallbacks = [
+            (DirKind::State, "APP_STATE_DIR"),
+            (DirKind::Cache, "APP_CACHE_DIR"),
+        ];
+        env::set_var("APP_STATE_DIR", "/srv/app/state");
+        env::set_var("APP_CACHE_DIR", "/srv/app/cache");
+        env::set_var("CACHE_DIRECTORY", "/var/cache/app");
+        let dirs = SystemdDirs::new_with_fallbacks(&fallbacks);
+        env::remove_var("APP_STATE_DIR");
+        env::remove_var("APP_CACHE_DIR");
+        env::remove_var("CACHE_DIRECTORY");
+
+        assert_eq!(dirs.state_dir(), Some(Path::new("/srv/app/state")));
+        assert_eq!(dirs.cache_dir(), Some(Path::new("/var/cache/app")));
+        assert_eq!(dirs.runtime_dir(), None);
+    }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[test]
+    fn test_all_under() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/srv/myapp/run")),
+            "STATE_DIRECTORY" => Some(String::from("/srv/myapp")),
+            _ => None,
+        });
+
+        assert!(dirs.all_under("/srv/myapp"));
+        assert!(!dirs.all_under("/srv/myapp/run"));
+        assert!(SystemdDirs::from_lookup(|_| None).all_under("/srv/myapp"));
+
+        let dirs = dirs.with_cache_dirs(vec![PathBuf::from("/srv/myappx/cache")]);
+        assert!(!dirs.all_under("/srv/myapp"));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_new_shared() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SystemdDirs>();
+
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo");
+        let dirs = SystemdDirs::new_shared();
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let dirs = Arc::clone(&dirs);
+                thread::spawn(move || dirs.runtime_dir().map(Path::to_path_buf))
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), Some(PathBuf::from("/run/foo")));
+        }
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_modified_since() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let [old, new] = ["old", "new"].map(|name| temp.path().join(name));
+        let since = SystemTime::now();
+        let hour = Duration::from_secs(60 * 60);
+        for (dir, mtime) in [(&old, since - hour), (&new, since + hour)] {
+            fs::create_dir(dir).unwrap();
+            fs::File::open(dir).unwrap().set_modified(mtime).unwrap();
+        }
+
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert!(dirs
+            .modified_since(DirKind::State, since)
+            .unwrap()
+            .is_empty());
+
+        let dirs = dirs.with_state_dirs(vec![old, new.clone()]);
+        assert_eq!(
+            dirs.modified_since(DirKind::State, since).unwrap(),
+            vec![new.as_path()]
+        );
+    }
+
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_systemd_dirs_utf8() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/café");
+        let dirs = SystemdDirsUtf8::new().unwrap();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CONFIGURATION_DIRECTORY");
+
+        assert_eq!(dirs.runtime_dir(), Some("/run/foo"));
+        assert_eq!(dirs.runtime_dirs(), vec!["/run/foo", "/run/bar"]);
+        assert_eq!(dirs.config_dir(), Some("/etc/café"));
+        assert_eq!(dirs.state_dir(), None);
+        assert!(dirs.cache_dirs().is_empty());
+    }
+
+    #[cfg(feature = "logs")]
+    #[cfg(unix)]
+    #[test]
+    fn test_systemd_dirs_utf8_not_unicode() {
+        use std::os::unix::ffi::OsStrExt;
+        let _env = lock_env();
+        env::set_var("LOGS_DIRECTORY", OsStr::from_bytes(b"/var/log/\xff"));
+        let result = SystemdDirsUtf8::new();
+        env::remove_var("LOGS_DIRECTORY");
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), DirKind::Logs);
+        assert_eq!(error.to_string(), "LOGS_DIRECTORY is not valid UTF-8");
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_kind_difference() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/x:/y")),
+            "STATE_DIRECTORY" => Some(String::from("/y:/z")),
+            _ => None,
+        });
+
+        assert_eq!(
+            dirs.kind_difference(DirKind::Runtime, DirKind::State),
+            vec![Path::new("/x")]
+        );
+        assert_eq!(
+            dirs.kind_difference(DirKind::State, DirKind::Runtime),
+            vec![Path::new("/z")]
+        );
+        assert_eq!(
+            dirs.kind_difference(DirKind::Runtime, DirKind::Cache),
+            vec![Path::new("/x"), Path::new("/y")]
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_find_config_file() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let [first, second] = ["first", "second"].map(|name| temp.path().join(name));
+        fs::create_dir(&first).unwrap();
+        fs::create_dir(&second).unwrap();
+        fs::create_dir(first.join("app.toml.d")).unwrap();
+        fs::write(second.join("app.toml"), "").unwrap();
+        fs::write(second.join("app.toml.d"), "").unwrap();
+
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert_eq!(dirs.find_config_file("app.toml"), None);
+
+        let dirs = dirs.with_config_dirs(vec![first, second.clone()]);
+        assert_eq!(
+            dirs.find_config_file("app.toml"),
+            Some(second.join("app.toml"))
+        );
+        assert_eq!(
+            dirs.find_config_file("app.toml.d"),
+            Some(second.join("app.toml.d"))
+        );
+        assert_eq!(dirs.find_config_file("missing.toml"), None);
     }
 
-    /// Returns all configuration directories as defined by `ConfigurationDirectory` in the unit file.
-    ///
-    /// If the environment variable `CONFIGURATION_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns an empty vector.
-    /// If it was set, it returns all paths in the colon-separated list.
-    /// To get just the first path, use [`Self::config_dir`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let config_dirs = dirs.config_dirs();
-    /// ```
-    pub fn config_dirs(&self) -> Vec<&Path> {
-        self.as_paths(&self.config_dirs).collect()
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_new_canonical_lexical() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "/run/foo:/run/bar/");
+        let clean = SystemdDirs::new_canonical_lexical();
+        let errors: Vec<_> = ["/run/foo:run/bar", "/run/foo/../bar", "/run/./foo"]
+            .into_iter()
+            .map(|value| {
+                env::set_var("STATE_DIRECTORY", value);
+                SystemdDirs::new_canonical_lexical().unwrap_err()
+            })
+            .collect();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("STATE_DIRECTORY");
+
+        assert!(clean.is_ok());
+        for (error, path) in errors
+            .iter()
+            .zip(["run/bar", "/run/foo/../bar", "/run/./foo"])
+        {
+            assert_eq!(error.kind(), DirKind::State);
+            assert_eq!(error.path(), Path::new(path));
+        }
     }
 
-    /// Returns the first configuration directory as defined by `ConfigurationDirectory` in the unit file.
-    ///
-    /// If the environment variable `CONFIGURATION_DIRECTORY` was not set when [`SystemdDirs`] was created, it returns [`None`].
-    /// If it was set, it returns the first path in the colon-separated list.
-    /// To get all paths, use [`Self::config_dirs`].
-    ///
-    /// # Examples
-    /// ```
-    /// let dirs = systemd_directories::SystemdDirs::new();
-    /// let config_dir = dirs.config_dir();
-    /// ```
-    pub fn config_dir(&self) -> Option<&Path> {
-        self.as_paths(&self.config_dirs).next()
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_dirs_with_existence() {
+        let _env = lock_env();
+        let temp = TempDir::new();
+        let missing = temp.path().join("missing");
+        let dirs = SystemdDirs::from_lookup(|_| None)
+            .with_runtime_dirs(vec![temp.path().to_path_buf(), missing.clone()]);
+
+        assert_eq!(
+            dirs.runtime_dirs_with_existence().unwrap(),
+            vec![(temp.path(), true), (missing.as_path(), false)]
+        );
+        assert!(dirs.state_dirs_with_existence().unwrap().is_empty());
     }
 
-    /// Helper to map a slice of `PathBuf` to an iterator of `&Path`.
-    fn as_paths<'a>(&'a self, dirs: &'a [PathBuf]) -> impl Iterator<Item = &Path> + 'a {
-        dirs.iter().map(|p| p.as_path())
+    #[cfg(all(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_as_project_dirs_like() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| {
+            Some(String::from(match key {
+                "STATE_DIRECTORY" => "/var/lib/foo",
+                "CACHE_DIRECTORY" => "/var/cache/foo",
+                "CONFIGURATION_DIRECTORY" => "/etc/foo",
+                _ => return None,
+            }))
+        });
+
+        let project_dirs = dirs.as_project_dirs_like().unwrap();
+        assert_eq!(project_dirs.config_dir(), Path::new("/etc/foo"));
+        assert_eq!(project_dirs.cache_dir(), Path::new("/var/cache/foo"));
+        assert_eq!(project_dirs.data_dir(), Path::new("/var/lib/foo"));
+        assert_eq!(project_dirs.runtime_dir(), None);
+
+        let dirs = dirs.with_runtime_dirs(vec![PathBuf::from("/run/foo")]);
+        let project_dirs = dirs.as_project_dirs_like().unwrap();
+        assert_eq!(project_dirs.runtime_dir(), Some(Path::new("/run/foo")));
+
+        let dirs = dirs.with_cache_dirs(Vec::new());
+        assert_eq!(dirs.as_project_dirs_like(), None);
     }
-}
 
-impl Default for SystemdDirs {
-    fn default() -> Self {
-        Self::new()
+    #[cfg(all(feature = "runtime", feature = "logs"))]
+    #[test]
+    fn test_to_environment_file() {
+        let _env = lock_env();
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/a:/run/b")),
+            "LOGS_DIRECTORY" => Some(String::from("/var/log/a")),
+            _ => None,
+        });
+
+        let contents = dirs.to_environment_file();
+        assert_eq!(
+            contents,
+            "RUNTIME_DIRECTORY=/run/a:/run/b\nLOGS_DIRECTORY=/var/log/a\n"
+        );
+
+        let vars = parse_env_file(&contents).unwrap();
+        assert!(SystemdDirs::from_lookup(|key| vars.get(key).cloned())
+            .changed_since(&dirs)
+            .is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use std::path::PathBuf;
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_new_trimmed() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", " : /run/foo\t");
+        let trimmed = SystemdDirs::new_trimmed();
+        let exact = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
 
-    fn test_set(
-        env_key: &str,
-        dirs: &[&str],
-        standalone_single: fn() -> Option<PathBuf>,
-        standalone_all: fn() -> Vec<PathBuf>,
-        method_single: fn(&SystemdDirs) -> Option<&Path>,
-        method_all: fn(&SystemdDirs) -> Vec<&Path>,
-    ) {
-        env::set_var(env_key, dirs.join(":"));
-        assert_eq!(standalone_single(), Some(PathBuf::from(dirs[0])));
+        assert_eq!(trimmed.runtime_dirs(), vec![Path::new("/run/foo")]);
+        assert_eq!(trimmed.raw_segment_info(DirKind::Runtime), Some((2, 1)));
+        assert_eq!(exact.raw_segment_info(DirKind::Runtime), Some((2, 2)));
         assert_eq!(
-            standalone_all(),
-            dirs.iter().map(PathBuf::from).collect::<Vec<PathBuf>>()
+            exact.runtime_dirs(),
+            vec![Path::new(" "), Path::new(" /run/foo\t")]
         );
-        let systemd_dirs = SystemdDirs::new();
-        assert_eq!(method_single(&systemd_dirs), Some(Path::new(dirs[0])));
+    }
+
+    #[cfg(all(feature = "state", feature = "cache", feature = "logs"))]
+    #[test]
+    fn test_diff_report() {
+        let _env = lock_env();
+        let previous = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/a:/run/b")),
+            "CACHE_DIRECTORY" => Some(String::from("/var/cache/old:/var/cache/kept")),
+            "LOGS_DIRECTORY" => Some(String::from("/var/log/old")),
+            _ => None,
+        });
+        let current = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some(String::from("/run/b:/run/a")),
+            "STATE_DIRECTORY" => Some(String::from("/var/lib/new")),
+            "CACHE_DIRECTORY" => Some(String::from("/var/cache/kept:/var/cache/new")),
+            _ => None,
+        });
+
         assert_eq!(
-            method_all(&systemd_dirs),
-            dirs.iter().map(Path::new).collect::<Vec<&Path>>()
+            current.diff_report(&previous),
+            vec![
+                "state: added /var/lib/new",
+                "cache: added /var/cache/new, removed /var/cache/old",
+                "logs: removed /var/log/old",
+            ]
         );
+        assert!(current.diff_report(&current).is_empty());
     }
 
-    fn test_unset(
-        env_key: &str,
-        standalone_single: fn() -> Option<PathBuf>,
-        standalone_all: fn() -> Vec<PathBuf>,
-        method_single: fn(&SystemdDirs) -> Option<&Path>,
-        method_all: fn(&SystemdDirs) -> Vec<&Path>,
-    ) {
-        env::remove_var(env_key);
-        assert_eq!(standalone_single(), None);
-        assert!(standalone_all().is_empty());
-        let systemd_dirs = SystemdDirs::new();
-        assert_eq!(method_single(&systemd_dirs), None);
-        assert!(method_all(&systemd_dirs).is_empty());
+    #[test]
+    fn test_split_escaped() {
+        assert_eq!(
+            split_escaped(r"/run/a\:b:/run/c", &[':']),
+            vec!["/run/a:b", "/run/c"]
+        );
+        assert_eq!(split_escaped(r"/a\\:/b", &[':']), vec![r"/a\", "/b"]);
+        assert_eq!(split_escaped(r"/a\b\", &[':']), vec![r"/a\b\"]);
+        assert_eq!(split_escaped("::", &[':']), vec!["", "", ""]);
     }
 
+    #[cfg(feature = "runtime")]
     #[test]
-    fn test_runtime_directory() {
-        test_set(
-            "RUNTIME_DIRECTORY",
-            &["/run/foo", "/run/bar"],
-            runtime_dir,
-            runtime_dirs,
-            SystemdDirs::runtime_dir,
-            SystemdDirs::runtime_dirs,
+    fn test_new_with_escapes() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", r"/run/a\:b:/run/c");
+        let escaped = SystemdDirs::new_with_escapes();
+        let exact = SystemdDirs::new();
+        env::remove_var("RUNTIME_DIRECTORY");
+
+        assert_eq!(
+            escaped.runtime_dirs(),
+            vec![Path::new("/run/a:b"), Path::new("/run/c")]
+        );
+        assert_eq!(escaped.raw_segment_info(DirKind::Runtime), Some((2, 2)));
+        assert_eq!(exact.raw_segment_info(DirKind::Runtime), Some((3, 3)));
+        assert_eq!(
+            exact.runtime_dirs(),
+            vec![Path::new(r"/run/a\"), Path::new("b"), Path::new("/run/c")]
         );
+    }
 
-        test_unset(
-            "RUNTIME_DIRECTORY",
-            runtime_dir,
-            runtime_dirs,
-            SystemdDirs::runtime_dir,
-            SystemdDirs::runtime_dirs,
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_persistence() {
+        let preserve = |value: &'static str| {
+            SystemdDirs::from_lookup(|key| {
+                (key == "RUNTIME_DIRECTORY_PRESERVE").then(|| value.into())
+            })
+            .persistence(DirKind::Runtime)
+        };
+
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        assert_eq!(dirs.persistence(DirKind::Runtime), Persistence::Ephemeral);
+        for kind in [
+            DirKind::State,
+            DirKind::Cache,
+            DirKind::Logs,
+            DirKind::Config,
+        ] {
+            assert_eq!(dirs.persistence(kind), Persistence::Persistent, "{kind}");
+        }
+
+        assert_eq!(preserve("yes"), Persistence::Persistent);
+        assert_eq!(preserve("Restart"), Persistence::UntilStop);
+        assert_eq!(preserve("no"), Persistence::Ephemeral);
+        assert_eq!(preserve("maybe"), Persistence::Ephemeral);
+
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY_PRESERVE", "restart");
+        let dirs = SystemdDirs::new();
+        env::set_var("RUNTIME_DIRECTORY_PRESERVE", "no");
+        let strict = SystemdDirs::new_strict().unwrap();
+        env::remove_var("RUNTIME_DIRECTORY_PRESERVE");
+        assert_eq!(dirs.persistence(DirKind::Runtime), Persistence::UntilStop);
+        assert_eq!(strict.persistence(DirKind::Runtime), Persistence::Ephemeral);
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state", feature = "config"))]
+    #[test]
+    fn test_dir_marked() {
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some("/run/a:/run/b".into()),
+            "CONFIGURATION_DIRECTORY" => Some("/etc/a".into()),
+            _ => None,
+        });
+
+        let runtime_dir = dirs.runtime_dir_marked().unwrap();
+        assert_eq!(runtime_dir.path(), Path::new("/run/a"));
+        assert_eq!(runtime_dir.join("app.sock"), Path::new("/run/a/app.sock"));
+        assert_eq!(
+            dirs.config_dir_marked().map(ConfigurationDirectory::path),
+            Some(Path::new("/etc/a"))
         );
+        assert_eq!(dirs.state_dir_marked(), None);
     }
 
+    #[cfg(feature = "config")]
+    #[cfg(unix)]
     #[test]
-    fn test_state_directory() {
-        test_set(
-            "STATE_DIRECTORY",
-            &["/var/lib/foo", "/var/lib/bar"],
-            state_dir,
-            state_dirs,
-            SystemdDirs::state_dir,
-            SystemdDirs::state_dirs,
+    fn test_config_dirs_readonly() {
+        let temp = TempDir::new();
+        let writable = temp.path().join("writable");
+        fs::create_dir(&writable).unwrap();
+        let read_only = read_only_dir(temp.path(), "read-only");
+        let paths: Vec<&Path> = iter::once(writable.as_path())
+            .chain(read_only.as_deref())
+            .collect();
+        let value = env::join_paths(&paths).unwrap().into_string().unwrap();
+        let dirs = SystemdDirs::from_lookup(|key| {
+            (key == "CONFIGURATION_DIRECTORY").then(|| value.clone())
+        });
+
+        let readonly = dirs.config_dirs_readonly().unwrap();
+        assert_eq!(readonly[0], (writable.clone(), false));
+        assert_eq!(fs::read_dir(&writable).unwrap().count(), 0);
+
+        // Root can write to read-only directories, so the read-only half is only checked for other users.
+        let expected: Vec<(PathBuf, bool)> = read_only.into_iter().map(|dir| (dir, true)).collect();
+        assert_eq!(readonly[1..], expected);
+
+        let missing = temp.path().join("missing");
+        let dirs = SystemdDirs::from_lookup(|key| {
+            (key == "CONFIGURATION_DIRECTORY").then(|| missing.to_str().unwrap().to_owned())
+        });
+        assert_eq!(
+            dirs.config_dirs_readonly().unwrap_err().kind(),
+            io::ErrorKind::NotFound
         );
+    }
 
-        test_unset(
-            "STATE_DIRECTORY",
-            state_dir,
-            state_dirs,
-            SystemdDirs::state_dir,
-            SystemdDirs::state_dirs,
+    #[cfg(all(
+        feature = "metrics",
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
+    #[test]
+    fn test_record_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let _env = lock_env();
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        env::set_var("RUNTIME_DIRECTORY", "/run/a:/run/b");
+        env::set_var("CONFIGURATION_DIRECTORY", "/etc/a");
+        metrics::with_local_recorder(&recorder, SystemdDirs::new);
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CONFIGURATION_DIRECTORY");
+
+        let gauges: Vec<String> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let DebugValue::Gauge(value) = value else {
+                    panic!("expected a gauge, got {value:?}");
+                };
+
+                let labels: Vec<String> = key
+                    .key()
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                format!("{}{{{}}} {}", key.key().name(), labels.join(","), value)
+            })
+            .collect();
+        assert_eq!(
+            gauges,
+            vec![
+                "systemd_directories_count{kind=runtime} 2",
+                "systemd_directories_count{kind=state} 0",
+                "systemd_directories_count{kind=cache} 0",
+                "systemd_directories_count{kind=logs} 0",
+                "systemd_directories_count{kind=config} 1",
+                "systemd_directories_total{} 3",
+            ]
         );
     }
 
+    #[cfg(any(
+        feature = "runtime",
+        feature = "state",
+        feature = "cache",
+        feature = "logs",
+        feature = "config"
+    ))]
     #[test]
-    fn test_cache_directory() {
-        test_set(
-            "CACHE_DIRECTORY",
-            &["/var/cache/foo", "/var/cache/bar"],
-            cache_dir,
-            cache_dirs,
-            SystemdDirs::cache_dir,
-            SystemdDirs::cache_dirs,
+    fn test_relative_to() {
+        let temp = TempDir::new();
+        let inside = temp.path().join("run/foo");
+        let outside = PathBuf::from("/run/bar");
+        assert_eq!(
+            relative_to(
+                &[inside, outside.clone(), temp.path().to_path_buf()],
+                temp.path()
+            ),
+            vec![PathBuf::from("run/foo"), outside, PathBuf::from(".")]
         );
+    }
 
-        test_unset(
-            "CACHE_DIRECTORY",
-            cache_dir,
-            cache_dirs,
-            SystemdDirs::cache_dir,
-            SystemdDirs::cache_dirs,
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[test]
+    fn test_dirs_relative_to_cwd() {
+        let cwd = env::current_dir().unwrap();
+        let inside = cwd.join("target/run");
+        let value = format!("{}:/run/outside", inside.to_str().unwrap());
+        let dirs =
+            SystemdDirs::from_lookup(|key| (key == "RUNTIME_DIRECTORY").then(|| value.clone()));
+        assert_eq!(
+            dirs.runtime_dirs_relative_to_cwd().unwrap(),
+            vec![PathBuf::from("target/run"), PathBuf::from("/run/outside")]
         );
+        assert!(dirs.state_dirs_relative_to_cwd().unwrap().is_empty());
     }
 
+    #[cfg(feature = "cache")]
     #[test]
-    fn test_logs_directory() {
-        test_set(
-            "LOGS_DIRECTORY",
-            &["/var/log/foo", "/var/log/bar"],
-            logs_dir,
-            logs_dirs,
-            SystemdDirs::logs_dir,
-            SystemdDirs::logs_dirs,
+    fn test_cache_key() {
+        let lookup = |cache: &'static str, state: &'static str| {
+            move |key: &str| match key {
+                "CACHE_DIRECTORY" => Some(cache.to_string()),
+                "STATE_DIRECTORY" => Some(state.to_string()),
+                _ => None,
+            }
+        };
+
+        let a = SystemdDirs::from_lookup(lookup("/var/cache/foo", "/var/lib/foo"));
+        let b = SystemdDirs::from_lookup(lookup("/var/cache/foo", "/var/lib/bar"));
+        let c = SystemdDirs::from_lookup(lookup("/var/cache/bar", "/var/lib/foo"));
+        assert_eq!(a.cache_key(), b.cache_key());
+        assert_ne!(a.cache_key(), c.cache_key());
+        assert_eq!(a.cache_key().len(), 16);
+        assert!(a.cache_key().bytes().all(|b| b.is_ascii_hexdigit()));
+    }
+
+    #[cfg(all(feature = "state", feature = "config"))]
+    #[test]
+    fn test_modes() {
+        let _env = lock_env();
+        env::set_var("STATE_DIRECTORY_MODE", "0750");
+        env::set_var("CONFIGURATION_DIRECTORY_MODE", "755");
+        env::set_var("CACHE_DIRECTORY_MODE", "garbage");
+        let modes = SystemdDirs::from_lookup(|_| None).modes();
+        env::remove_var("STATE_DIRECTORY_MODE");
+        env::remove_var("CONFIGURATION_DIRECTORY_MODE");
+        env::remove_var("CACHE_DIRECTORY_MODE");
+
+        assert_eq!(modes.get(DirKind::State), Some(0o750));
+        assert_eq!(modes.get(DirKind::Config), Some(0o755));
+        assert_eq!(modes.get(DirKind::Cache), None);
+        assert_eq!(modes.get(DirKind::Runtime), None);
+        assert_eq!(modes.get(DirKind::Logs), None);
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_walk_files() {
+        let temp = TempDir::new();
+        let first = temp.path().join("first");
+        let second = temp.path().join("second");
+        fs::create_dir_all(first.join("nested/deeper")).unwrap();
+        fs::create_dir_all(second.join("empty")).unwrap();
+        fs::write(first.join("b"), "").unwrap();
+        fs::write(first.join("nested/a"), "").unwrap();
+        fs::write(first.join("nested/deeper/c"), "").unwrap();
+        fs::write(second.join("d"), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&first, second.join("link")).unwrap();
+
+        let dirs =
+            SystemdDirs::from_lookup(|_| None).with_state_dirs(vec![first.clone(), second.clone()]);
+        assert_eq!(
+            dirs.walk_files(DirKind::State).unwrap(),
+            vec![
+                first.join("b"),
+                first.join("nested/a"),
+                first.join("nested/deeper/c"),
+                second.join("d"),
+            ]
         );
+        assert!(dirs.walk_files(DirKind::Cache).unwrap().is_empty());
 
-        test_unset(
-            "LOGS_DIRECTORY",
-            logs_dir,
-            logs_dirs,
-            SystemdDirs::logs_dir,
-            SystemdDirs::logs_dirs,
+        let dirs = dirs.with_state_dirs(vec![temp.path().join("missing")]);
+        assert!(dirs.walk_files(DirKind::State).is_err());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "state"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_runtime_state_colocated() {
+        let temp = TempDir::new();
+        let runtime = temp.path().join("runtime");
+        let state = temp.path().join("state");
+        fs::create_dir(&runtime).unwrap();
+        fs::create_dir(&state).unwrap();
+
+        let dirs = SystemdDirs::from_lookup(|_| None).with_runtime_dirs(vec![runtime.clone()]);
+        assert_eq!(dirs.runtime_state_colocated().unwrap(), None);
+
+        let dirs = dirs.with_state_dirs(vec![state]);
+        assert_eq!(dirs.runtime_state_colocated().unwrap(), Some(true));
+
+        // procfs is always its own filesystem on Linux, unlike any pair of temporary directories.
+        #[cfg(target_os = "linux")]
+        {
+            let dirs = dirs.clone().with_state_dirs(vec![PathBuf::from("/proc")]);
+            assert_eq!(dirs.runtime_state_colocated().unwrap(), Some(false));
+        }
+
+        let dirs = dirs.with_state_dirs(vec![temp.path().join("missing")]);
+        assert!(dirs.runtime_state_colocated().is_err());
+    }
+
+    #[cfg(all(feature = "runtime", feature = "cache"))]
+    #[test]
+    fn test_builder_treat_empty_as_unset() {
+        let _env = lock_env();
+        env::set_var("RUNTIME_DIRECTORY", "");
+        env::set_var("CACHE_DIRECTORY", ":");
+        let exact = SystemdDirs::builder().build();
+        let unset = SystemdDirs::builder().treat_empty_as_unset(true).build();
+        let set = SystemdDirs::builder()
+            .treat_empty_as_unset(true)
+            .treat_empty_as_unset(false)
+            .build();
+        env::remove_var("RUNTIME_DIRECTORY");
+        env::remove_var("CACHE_DIRECTORY");
+
+        assert_eq!(exact.present_vars(), vec![DirKind::Runtime, DirKind::Cache]);
+        assert_eq!(exact.raw_segment_info(DirKind::Runtime), Some((1, 0)));
+        assert_eq!(set, exact);
+
+        assert_eq!(unset.present_vars(), vec![DirKind::Cache]);
+        assert_eq!(unset.check_empty(), vec![DirKind::Cache]);
+        assert_eq!(unset.raw_segment_info(DirKind::Runtime), None);
+        assert!(unset.runtime_dirs().is_empty());
+    }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_effective_mode() {
+        assert_eq!(
+            parse_umask("Name:\tfoo\nUmask:\t0022\nState:\tR\n"),
+            Some(0o022)
         );
+        assert_eq!(parse_umask("Umask:\t0077"), Some(0o077));
+        assert_eq!(parse_umask("Name:\tfoo\n"), None);
+
+        let _env = lock_env();
+        let umask = parse_umask(&fs::read_to_string("/proc/self/status").unwrap()).unwrap();
+        env::set_var("STATE_DIRECTORY_MODE", "0777");
+        env::set_var("CACHE_DIRECTORY_MODE", "2750");
+        let dirs = SystemdDirs::from_lookup(|_| None);
+        let state = dirs.effective_mode(DirKind::State);
+        let cache = dirs.effective_mode(DirKind::Cache);
+        let runtime = dirs.effective_mode(DirKind::Runtime);
+        env::remove_var("STATE_DIRECTORY_MODE");
+        env::remove_var("CACHE_DIRECTORY_MODE");
+
+        assert_eq!(state, Some(0o777 & !umask));
+        assert_eq!(cache, Some(0o2750 & !umask));
+        assert_eq!(runtime, None);
     }
 
     #[test]
-    fn test_configuration_directory() {
-        test_set(
-            "CONFIGURATION_DIRECTORY",
-            &["/etc/foo", "/etc/bar"],
-            config_dir,
-            config_dirs,
-            SystemdDirs::config_dir,
-            SystemdDirs::config_dirs,
+    fn test_parse_colon_paths() {
+        for value in [
+            "",
+            ":",
+            "/run/foo",
+            "::/run/foo:/run/bar::",
+            " /run/foo : /run/bar ",
+        ] {
+            assert_eq!(
+                parse_colon_paths(OsStr::new(value)),
+                ColonSeparatedPaths::new(value.to_string()).parse(&ParseOptions::default()),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_colon_paths_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        assert_eq!(
+            parse_colon_paths(OsStr::from_bytes(b"/run/\xff::/run/foo")),
+            vec![
+                PathBuf::from(OsStr::from_bytes(b"/run/\xff")),
+                PathBuf::from("/run/foo")
+            ]
         );
+    }
 
-        test_unset(
-            "CONFIGURATION_DIRECTORY",
-            config_dir,
-            config_dirs,
-            SystemdDirs::config_dir,
-            SystemdDirs::config_dirs,
+    #[cfg(all(feature = "runtime", feature = "state", feature = "cache"))]
+    #[test]
+    fn test_dir_shortest() {
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some("/run/a/b/c:/run/x:/run/y".into()),
+            "STATE_DIRECTORY" => Some("/var/lib/a".into()),
+            _ => None,
+        });
+
+        assert_eq!(dirs.runtime_dir_shortest(), Some(Path::new("/run/x")));
+        assert_eq!(dirs.state_dir_shortest(), Some(Path::new("/var/lib/a")));
+        assert_eq!(dirs.cache_dir_shortest(), None);
+    }
+
+    #[cfg(all(feature = "tracing", feature = "runtime", feature = "config"))]
+    #[test]
+    fn test_record_fields() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Id, Record};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        /// A layer that records the string fields recorded on any span.
+        #[derive(Clone, Default)]
+        struct Recording(Arc<Mutex<Vec<(&'static str, String)>>>);
+
+        impl Visit for Recording {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((field.name(), value.to_string()));
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.record_str(field, &format!("{value:?}"));
+            }
+        }
+
+        impl<S: tracing::Subscriber> Layer<S> for Recording {
+            fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+                values.record(&mut self.clone());
+            }
+        }
+
+        let dirs = SystemdDirs::from_lookup(|key| match key {
+            "RUNTIME_DIRECTORY" => Some("/run/a:/run/b".into()),
+            "CONFIGURATION_DIRECTORY" => Some("/etc/a".into()),
+            "CACHE_DIRECTORY" => Some(":".into()),
+            "STATE_DIRECTORY" => Some("/var/lib/a".into()),
+            _ => None,
+        });
+
+        let recording = Recording::default();
+        let subscriber = tracing_subscriber::registry().with(recording.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "service",
+                runtime_dir = tracing::field::Empty,
+                cache_dir = tracing::field::Empty,
+                config_dir = tracing::field::Empty,
+            );
+            dirs.record_fields(&span);
+        });
+        assert_eq!(
+            *recording.0.lock().unwrap(),
+            vec![
+                ("runtime_dir", "/run/a:/run/b".to_string()),
+                ("config_dir", "/etc/a".to_string()),
+            ]
         );
     }
+
+    #[cfg(all(feature = "state", feature = "cache"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_inaccessible_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp = TempDir::new();
+        let accessible = temp.path().join("accessible");
+        let stripped = temp.path().join("stripped");
+        fs::create_dir(&accessible).unwrap();
+        fs::create_dir(&stripped).unwrap();
+        fs::set_permissions(&stripped, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let missing = temp.path().join("missing");
+        let dirs = SystemdDirs::from_lookup(|_| None)
+            .with_state_dirs(vec![accessible.clone()])
+            .with_cache_dirs(vec![stripped.clone(), missing.clone()]);
+        let inaccessible = dirs.inaccessible_dirs();
+        let is_root = fs::metadata(stripped.join(".")).is_ok();
+        fs::set_permissions(&stripped, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Neither accessible nor missing directories are reported, whoever runs the test.
+        assert!(inaccessible
+            .iter()
+            .all(|(_, dir)| *dir != accessible && *dir != missing));
+
+        // Permissions are not enforced for root, so as root the stripped directory is accessible, nothing is reported,
+        // and the check for the stripped directory is skipped.
+        if is_root {
+            assert!(inaccessible.is_empty());
+            return;
+        }
+
+        assert_eq!(inaccessible, vec![(DirKind::Cache, stripped)]);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_search_path() {
+        let temp = TempDir::new();
+        let [first, second] = ["first", "second"].map(|name| temp.path().join(name));
+        fs::create_dir(&first).unwrap();
+        fs::create_dir(&second).unwrap();
+        fs::write(first.join("a.toml"), "").unwrap();
+        fs::write(second.join("a.toml"), "").unwrap();
+        fs::write(second.join("b.toml"), "").unwrap();
+
+        let dirs = SystemdDirs::from_lookup(|_| None)
+            .with_config_dirs(vec![first.clone(), second.clone()]);
+        let search_path = dirs.config_search_path();
+        assert_eq!(search_path.dirs(), [first.clone(), second.clone()]);
+        assert_eq!(search_path.find("a.toml"), Some(first.join("a.toml")));
+        assert_eq!(search_path.find("b.toml"), Some(second.join("b.toml")));
+        assert_eq!(search_path.find("c.toml"), None);
+        assert_eq!(SearchPath::default().find("a.toml"), None);
+    }
+
+    #[cfg(feature = "state")]
+    #[test]
+    fn test_is_writable_concurrent() {
+        let temp = TempDir::new();
+        fs::write(
+            temp.path()
+                .join(format!(".systemd-directories-probe-{}-0", process::id())),
+            "",
+        )
+        .unwrap();
+
+        std::thread::scope(|scope| {
+            let probes: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| is_writable(temp.path())))
+                .collect();
+            for probe in probes {
+                assert!(probe.join().unwrap().unwrap());
+            }
+        });
+
+        assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 1);
+    }
 }